@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::keystroke::is_modifier;
+use crate::layout::{KeyboardLayout, ShiftState};
+use crate::schedule::{total_duration, TypingScheduler};
+use crate::source_map::SourceMap;
+use crate::virtual_key::VirtualKey;
+use crate::KeyError;
+
+/// How much typing a piece of text takes on a layout, for estimating long jobs before running them.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct TypingStats {
+    /// Characters in the text.
+    pub characters: usize,
+    /// Keystrokes in the translation, modifiers included.
+    pub events: usize,
+    /// Modifier presses and releases inserted by the translation.
+    pub modifier_changes: usize,
+    /// The part of [`TypingStats::modifier_changes`] that presses or releases shift.
+    pub shift_toggles: usize,
+    /// Characters that need a modifier held for any of their keys, such as uppercase letters on most
+    /// layouts or accents whose dead key is shifted.
+    pub modified_characters: usize,
+    /// How many times each key is pressed, modifiers included.
+    pub key_counts: BTreeMap<VirtualKey, usize>,
+    /// The total time the translation takes with the scheduler passed to [`TypingStats::analyze`],
+    /// [`Duration::MAX`] when it is longer than that.
+    pub estimated_duration: Duration,
+}
+
+impl TypingStats {
+    /// Translates `text` with `layout` and measures the result, timing it with `scheduler`.
+    pub fn analyze(text: &str, layout: &KeyboardLayout, scheduler: &TypingScheduler) -> Result<TypingStats, KeyError> {
+        let map = SourceMap::new(text, layout)?;
+        let keystrokes = map.keystrokes();
+
+        let mut stats = TypingStats {
+            characters: text.chars().count(),
+            events: keystrokes.len(),
+            estimated_duration: total_duration(&scheduler.schedule_keystrokes(&keystrokes, layout)),
+            ..TypingStats::default()
+        };
+
+        // In the flat format every other occurrence of a modifier is a press
+        let mut is_held = [false; 256];
+        for &vk in &keystrokes {
+            let vk = VirtualKey(vk);
+            if is_modifier(vk) {
+                stats.modifier_changes += 1;
+                if vk == VirtualKey::SHIFT {
+                    stats.shift_toggles += 1;
+                }
+                is_held[vk.0 as usize] = !is_held[vk.0 as usize];
+                if !is_held[vk.0 as usize] {
+                    continue
+                }
+            }
+            *stats.key_counts.entry(vk).or_default() += 1;
+        }
+
+        stats.modified_characters = text.chars()
+            .filter_map(|character| layout.keys_for(character))
+            .filter(|plan| plan.keys().any(|(_, shift_state)| !shift_state.difference(ShiftState::HANKAKU).is_empty()))
+            .count();
+
+        Ok(stats)
+    }
+
+    /// The share of characters that need a modifier, from `0.0` to `1.0`.
+    pub fn modified_ratio(&self) -> f64 {
+        if self.characters == 0 {
+            return 0.0
+        }
+        self.modified_characters as f64 / self.characters as f64
+    }
+
+    /// Keys ordered from most to least pressed, ties broken by key code.
+    pub fn most_used_keys(&self) -> Vec<(VirtualKey, usize)> {
+        let mut keys: Vec<(VirtualKey, usize)> = self.key_counts.iter().map(|(&vk, &count)| (vk, count)).collect();
+        keys.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        keys
+    }
+}
+
+
+
+/* ### --- UNIT TEST --- ### */
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analyze(text: &str) -> TypingStats {
+        TypingStats::analyze(text, KeyboardLayout::us(), &TypingScheduler::at_speed(10.0)).unwrap()
+    }
+
+    #[test]
+    fn test_counts() {
+        // ⇧ H ⇧ E L L O OEM_COMMA SPACE ⇧ W ⇧ O R L D ⇧ 1 ⇧
+        let stats = analyze("Hello, World!");
+
+        assert_eq!(stats.characters, 13);
+        assert_eq!(stats.events, 19);
+        assert_eq!(stats.modifier_changes, 6);
+        assert_eq!(stats.shift_toggles, 6);
+        assert_eq!(stats.modified_characters, 3);
+        assert_eq!(stats.key_counts[&VirtualKey::SHIFT], 3);
+        assert_eq!(stats.key_counts[&VirtualKey::KEY_L], 3);
+        assert_eq!(stats.most_used_keys()[..2], [(VirtualKey::SHIFT, 3), (VirtualKey::KEY_L, 3)]);
+    }
+
+    #[test]
+    fn test_shift_runs_count_once() {
+        let stats = analyze("ABCDEF");
+
+        assert_eq!(stats.shift_toggles, 2);
+        assert_eq!(stats.modified_characters, 6);
+        assert_eq!(stats.modified_ratio(), 1.0);
+    }
+
+    #[test]
+    fn test_estimated_duration() {
+        let scheduler = TypingScheduler { modifier_gap: Duration::from_millis(10), ..TypingScheduler::at_speed(10.0) };
+        let stats = TypingStats::analyze("aB", KeyboardLayout::us(), &scheduler).unwrap();
+
+        assert_eq!(stats.estimated_duration, Duration::from_millis(220));
+    }
+
+    #[test]
+    fn test_estimated_duration_saturates() {
+        let stats = TypingStats::analyze("Hello", KeyboardLayout::us(), &TypingScheduler::at_speed(1e-30)).unwrap();
+
+        assert_eq!(stats.estimated_duration, Duration::MAX);
+    }
+
+    #[test]
+    fn test_dead_keys() {
+        // ´ E, then ⇧↓ ` ⇧↑ E
+        let stats = TypingStats::analyze("éè", KeyboardLayout::german(), &TypingScheduler::at_speed(10.0)).unwrap();
+
+        assert_eq!(stats.characters, 2);
+        assert_eq!(stats.events, 6);
+        assert_eq!(stats.modified_characters, 1);
+        assert_eq!(stats.key_counts[&VirtualKey::OEM_6], 2);
+        assert_eq!(stats.key_counts[&VirtualKey::KEY_E], 2);
+    }
+
+    #[test]
+    fn test_empty_text() {
+        let stats = analyze("");
+
+        assert_eq!(stats, TypingStats::default());
+        assert_eq!(stats.modified_ratio(), 0.0);
+    }
+
+    #[test]
+    fn test_unmapped_character() {
+        assert!(TypingStats::analyze("ü", KeyboardLayout::us(), &TypingScheduler::default()).is_err());
+    }
+}
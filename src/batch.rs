@@ -0,0 +1,190 @@
+use crate::keystroke::{decode_keystrokes, is_modifier, Keystroke};
+use crate::sender::KeystrokeSender;
+use crate::virtual_key::VirtualKey;
+
+/// Splits events into batches for senders that deliver several events per call, like one
+/// `SendInput` call per batch.
+///
+/// Modifiers still held when a batch ends are released at the end of it and pressed again at the
+/// start of the next one, so every batch is balanced and the target never sees shift held across a
+/// pause. A modifier press always arrives in the same batch as the key it applies to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Batcher {
+    /// The most events in one batch, counting the inserted modifier presses and releases. A key that
+    /// needs more events than this with its modifiers gets a batch of its own. Zero is treated as one.
+    pub max_events: usize,
+}
+
+impl Default for Batcher {
+    fn default() -> Self {
+        Batcher::new(64)
+    }
+}
+
+impl Batcher {
+    pub fn new(max_events: usize) -> Batcher {
+        Batcher { max_events }
+    }
+
+    /// Splits `keystrokes` into consecutive batches. Together they type the same keys with the same
+    /// modifiers held, with extra modifier releases and presses at the batch boundaries.
+    pub fn split(&self, keystrokes: &[Keystroke]) -> Vec<Vec<Keystroke>> {
+        let max_events = self.max_events.max(1);
+        let mut batches = Vec::new();
+        let mut held: Vec<VirtualKey> = Vec::new();
+        let mut batch: Vec<Keystroke> = Vec::new();
+        let mut pressed_again = 0;
+        let mut start = 0;
+
+        while start < keystrokes.len() {
+            let end = unit_end(keystrokes, start);
+            let unit = &keystrokes[start..end];
+            start = end;
+
+            let mut held_after = held.clone();
+            for &keystroke in unit {
+                track_modifier(&mut held_after, keystroke);
+            }
+
+            // A release never makes the batch longer than ending it would, so it never starts one
+            let is_release = matches!(unit, [Keystroke::Up(vk)] if is_modifier(*vk));
+            if !is_release && batch.len() > pressed_again && batch.len() + unit.len() + held_after.len() > max_events {
+                batch.extend(held.iter().rev().map(|&vk| Keystroke::Up(vk)));
+                batches.push(std::mem::take(&mut batch));
+                batch.extend(held.iter().map(|&vk| Keystroke::Down(vk)));
+                pressed_again = batch.len();
+            }
+
+            batch.extend_from_slice(unit);
+            held = held_after;
+        }
+        if batch.len() > pressed_again {
+            batches.push(batch);
+        }
+
+        batches
+    }
+
+    /// Sends `keystrokes` one batch at a time through [`KeystrokeSender::send_batch`]. `between` runs
+    /// before every batch but the first, which is the place to pause so a slow target can catch up.
+    pub fn send<S, F>(&self, sender: &mut S, keystrokes: &[Keystroke], mut between: F) -> Result<(), S::Error>
+    where
+        S: KeystrokeSender,
+        F: FnMut(&mut S, usize) -> Result<(), S::Error>,
+    {
+        for (i, batch) in self.split(keystrokes).into_iter().enumerate() {
+            if i > 0 {
+                between(sender, i)?;
+            }
+            sender.send_batch(&batch)?;
+        }
+        Ok(())
+    }
+
+    /// Sends the flat output of [`crate::to_keystrokes_new`] in batches, see [`Batcher::send`].
+    pub fn send_keystrokes<S, F>(&self, sender: &mut S, keystrokes: &[u8], between: F) -> Result<(), S::Error>
+    where
+        S: KeystrokeSender,
+        F: FnMut(&mut S, usize) -> Result<(), S::Error>,
+    {
+        self.send(sender, &decode_keystrokes(keystrokes), between)
+    }
+}
+
+/// Tracks the modifiers held down, in the order they were pressed.
+fn track_modifier(held: &mut Vec<VirtualKey>, keystroke: Keystroke) {
+    match keystroke {
+        Keystroke::Down(vk) if is_modifier(vk) && !held.contains(&vk) => held.push(vk),
+        Keystroke::Up(vk) => held.retain(|&known| known != vk),
+        _ => {}
+    }
+}
+
+/// The end of the smallest run starting at `start` that may not be split, which is any modifier
+/// presses followed by the event they apply to.
+fn unit_end(keystrokes: &[Keystroke], start: usize) -> usize {
+    let mut end = start;
+    while let Some(&Keystroke::Down(vk)) = keystrokes.get(end) && is_modifier(vk) {
+        end += 1;
+    }
+    (end + 1).min(keystrokes.len())
+}
+
+
+
+/* ### --- UNIT TEST --- ### */
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sender::RecordingSender;
+    use crate::validate::{canonicalize, validate};
+    use std::time::Duration;
+
+    fn events(text: &str) -> Vec<Keystroke> {
+        decode_keystrokes(&crate::to_keystrokes_new(text).unwrap())
+    }
+
+    #[test]
+    fn test_batches_cover_everything() {
+        let keystrokes = events("Hello, World! ABC def");
+
+        for max_events in 1..8 {
+            let batches = Batcher::new(max_events).split(&keystrokes);
+            assert_eq!(canonicalize(&batches.concat()), keystrokes);
+            for batch in &batches {
+                // Only a single key with its modifiers may go over the limit
+                let keys = batch.iter().filter(|keystroke| !is_modifier(keystroke.vk())).count();
+                assert!(batch.len() <= max_events || keys == 1, "{max_events}: {batch:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_no_modifier_held_between_batches() {
+        let keystrokes = events("aBcDeFgH {}|~ XyZ ABCDEFGH");
+
+        for max_events in 1..10 {
+            for batch in Batcher::new(max_events).split(&keystrokes) {
+                assert_eq!(validate(&batch), Ok(()), "{max_events}: {batch:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_modifier_stays_with_its_key() {
+        // ⇧↓ H ⇧↑ I
+        let keystrokes = events("Hi");
+
+        assert_eq!(Batcher::new(1).split(&keystrokes), vec![keystrokes[..3].to_vec(), keystrokes[3..].to_vec()]);
+        assert_eq!(Batcher::new(4).split(&keystrokes), vec![keystrokes.clone()]);
+    }
+
+    #[test]
+    fn test_held_shift_is_released_and_pressed_again() {
+        let batches = Batcher::new(2).split(&events("ABC"));
+
+        assert_eq!(batches, vec![events("A"), events("B"), events("C")]);
+    }
+
+    #[test]
+    fn test_long_chord_gets_its_own_batch() {
+        let keystrokes = crate::parse_notation("a ⌃↓ ⎇↓ ⇧↓ E ⇧↑ ⎇↑ ⌃↑").unwrap();
+
+        assert_eq!(Batcher::new(2).split(&keystrokes), vec![keystrokes[..1].to_vec(), keystrokes[1..].to_vec()]);
+    }
+
+    #[test]
+    fn test_send_pauses_between_batches() {
+        let mut sender = RecordingSender::new();
+        Batcher::new(4).send_keystrokes(&mut sender, &crate::to_keystrokes_new("abcdefghij").unwrap(), |sender, _| {
+            sender.clock_mut().advance(Duration::from_millis(50));
+            Ok(())
+        }).unwrap();
+
+        let times: Vec<u64> = sender.events().iter().map(|&(at, _)| at.as_millis() as u64).collect();
+        assert_eq!(times, vec![0, 0, 0, 0, 50, 50, 50, 50, 100, 100]);
+    }
+}
@@ -0,0 +1,210 @@
+use crate::keystroke::is_modifier;
+use crate::layout::{to_keystrokes_with_layout, KeyPlan, KeyboardLayout, ShiftState};
+use crate::simulator::decode_text;
+use crate::virtual_key::VirtualKey;
+use crate::KeyError;
+
+/// How keystrokes reach the target machine, which decides what goes wrong when its layout differs.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ReplayMode {
+    /// Keys are sent as virtual keys, like `SendInput` with `wVk` set. Letters and digits keep their
+    /// keys on every Latin layout, so mostly symbols come out wrong.
+    VirtualKey,
+    /// Keys are sent as scan codes, like `SendInput` with `KEYEVENTF_SCANCODE` or a hardware
+    /// keyboard emulator. Physical positions are kept, so on German `y` and `z` swap.
+    ScanCode,
+}
+
+/// A character both layouts type with a single key, but with different keys.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MovedCharacter {
+    pub character: char,
+    /// The key and modifiers on the first layout.
+    pub from: (VirtualKey, ShiftState),
+    /// The key and modifiers on the second layout.
+    pub to: (VirtualKey, ShiftState),
+}
+
+/// The differences between the characters two layouts type with a single key.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LayoutDiff {
+    /// Characters typed with another key or other modifiers. With [`ReplayMode::ScanCode`] a key
+    /// counts as another key when it sits somewhere else on the keyboard.
+    pub moved: Vec<MovedCharacter>,
+    /// Characters the first layout types with a single key and the second only with a dead key
+    /// followed by a base key, like `^` on German.
+    pub composed: Vec<char>,
+    /// Characters the first layout types with a single key and the second cannot type at all.
+    pub missing: Vec<char>,
+    /// Characters the second layout types with a single key and the first cannot type at all.
+    pub added: Vec<char>,
+}
+
+impl LayoutDiff {
+    /// Compares `from` with `to`, listing characters in the order `from` and then `to` defines them.
+    pub fn new(from: &KeyboardLayout, to: &KeyboardLayout, mode: ReplayMode) -> LayoutDiff {
+        let mut diff = LayoutDiff::default();
+
+        for mapping in from.mappings() {
+            // Only the mapping the lookups use counts when several type the same character
+            if from.lookup(mapping.character) != Some(mapping) {
+                continue
+            }
+            let other = match to.keys_for(mapping.character) {
+                Some(KeyPlan::Mapped(other)) => other,
+                Some(KeyPlan::Composed(..)) => {
+                    diff.composed.push(mapping.character);
+                    continue
+                }
+                None => {
+                    diff.missing.push(mapping.character);
+                    continue
+                }
+            };
+
+            let is_moved = match mode {
+                ReplayMode::VirtualKey => other.vk != mapping.vk,
+                ReplayMode::ScanCode => to.scan_code_for(other.vk) != from.scan_code_for(mapping.vk),
+            };
+            if is_moved || other.shift_state != mapping.shift_state {
+                diff.moved.push(MovedCharacter {
+                    character: mapping.character,
+                    from: (mapping.vk, mapping.shift_state),
+                    to: (other.vk, other.shift_state),
+                });
+            }
+        }
+
+        for mapping in to.mappings() {
+            if from.keys_for(mapping.character).is_none() && !diff.added.contains(&mapping.character) {
+                diff.added.push(mapping.character);
+            }
+        }
+
+        diff
+    }
+
+    /// Whether the layouts type every character the same way.
+    pub fn is_empty(&self) -> bool {
+        self.moved.is_empty() && self.composed.is_empty() && self.missing.is_empty() && self.added.is_empty()
+    }
+}
+
+/// The text that appears when `keystrokes` built for `source` are typed while `target` is the active
+/// layout.
+pub fn predict_text(keystrokes: &[u8], source: &KeyboardLayout, target: &KeyboardLayout, mode: ReplayMode) -> String {
+    match mode {
+        ReplayMode::VirtualKey => decode_text(keystrokes, target),
+        ReplayMode::ScanCode => {
+            // Modifiers sit in the same place on every layout
+            let remapped: Vec<u8> = keystrokes.iter()
+                .map(|&code| VirtualKey(code))
+                .map(|vk| match source.scan_code_for(vk).and_then(|scan| target.key_for_scan_code(scan)) {
+                    Some(remapped) if !is_modifier(vk) => remapped,
+                    _ => vk,
+                })
+                .map(|vk| vk.0)
+                .collect();
+            decode_text(&remapped, target)
+        }
+    }
+}
+
+/// Translates `text` for `source` and predicts what typing it with `target` active produces, see
+/// [`predict_text`].
+pub fn predict_mojibake(text: &str, source: &KeyboardLayout, target: &KeyboardLayout, mode: ReplayMode) -> Result<String, KeyError> {
+    let keystrokes = to_keystrokes_with_layout(text, source)?;
+    Ok(predict_text(&keystrokes, source, target, mode))
+}
+
+/// Finds which of `candidates` was active, and how keys were sent, when `text` translated for
+/// `source` came out as `observed`. Every layout and mode that reproduces `observed` exactly is
+/// returned, in the order of `candidates` with virtual keys before scan codes.
+pub fn matching_layouts<'a>(
+    text: &str,
+    observed: &str,
+    source: &KeyboardLayout,
+    candidates: impl IntoIterator<Item = &'a KeyboardLayout>,
+) -> Result<Vec<(&'a KeyboardLayout, ReplayMode)>, KeyError> {
+    let keystrokes = to_keystrokes_with_layout(text, source)?;
+    let mut matches = Vec::new();
+    for target in candidates {
+        for mode in [ReplayMode::VirtualKey, ReplayMode::ScanCode] {
+            if predict_text(&keystrokes, source, target, mode) == observed {
+                matches.push((target, mode));
+            }
+        }
+    }
+    Ok(matches)
+}
+
+
+
+/* ### --- UNIT TEST --- ### */
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn us() -> &'static KeyboardLayout {
+        KeyboardLayout::us()
+    }
+
+    fn german() -> &'static KeyboardLayout {
+        KeyboardLayout::german()
+    }
+
+    #[test]
+    fn test_same_layout_has_no_diff() {
+        assert!(LayoutDiff::new(us(), us(), ReplayMode::ScanCode).is_empty());
+        assert_eq!(predict_mojibake("Hello, World!", us(), us(), ReplayMode::ScanCode).unwrap(), "Hello, World!");
+    }
+
+    #[test]
+    fn test_diff_us_german() {
+        let moved = |diff: &LayoutDiff, character: char| diff.moved.iter().find(|moved| moved.character == character).copied();
+
+        let diff = LayoutDiff::new(us(), german(), ReplayMode::VirtualKey);
+        assert_eq!(moved(&diff, 'y'), None);
+        assert_eq!(moved(&diff, '@'), Some(MovedCharacter {
+            character: '@',
+            from: (VirtualKey::KEY_2, ShiftState::SHIFT),
+            to: (VirtualKey::KEY_Q, ShiftState::CTRL | ShiftState::ALT),
+        }));
+        assert_eq!(diff.composed, ['^', '`']);
+        assert!(diff.missing.is_empty());
+        assert!(diff.added.contains(&'ß') && diff.added.contains(&'€'));
+
+        let diff = LayoutDiff::new(us(), german(), ReplayMode::ScanCode);
+        assert!(moved(&diff, 'y').is_some() && moved(&diff, 'z').is_some());
+        assert_eq!(moved(&diff, 'a'), None);
+
+        let diff = LayoutDiff::new(german(), us(), ReplayMode::VirtualKey);
+        assert!(diff.composed.is_empty());
+        assert!(diff.missing.contains(&'ß') && diff.missing.contains(&'€'));
+        assert!(!diff.added.contains(&'^'));
+    }
+
+    #[test]
+    fn test_predict_mojibake() {
+        assert_eq!(predict_mojibake("y-z [x]", us(), german(), ReplayMode::VirtualKey).unwrap(), "y-z ßx");
+        assert_eq!(predict_mojibake("y-z [x]", us(), german(), ReplayMode::ScanCode).unwrap(), "z-y üx+");
+        assert_eq!(predict_mojibake("Grüße", german(), us(), ReplayMode::VirtualKey).unwrap(), "Gr;[e");
+    }
+
+    #[test]
+    fn test_matching_layouts() {
+        let candidates = [us(), german()];
+
+        let matches = matching_layouts("Yes?", "Zes_", us(), candidates).unwrap();
+        let matches: Vec<(&str, ReplayMode)> = matches.into_iter().map(|(layout, mode)| (layout.name(), mode)).collect();
+        assert_eq!(matches, [("German", ReplayMode::ScanCode)]);
+        assert_eq!(matching_layouts("ok", "ok", us(), candidates).unwrap().len(), 4);
+        assert!(matching_layouts("ok", "nope", us(), candidates).unwrap().is_empty());
+    }
+}
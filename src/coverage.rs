@@ -0,0 +1,182 @@
+use crate::layout::{KeyPlan, KeyboardLayout, ShiftState};
+use crate::KeyError;
+
+/// How a character would be typed on a layout.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Strategy {
+    /// A single key, alone or with shift.
+    Direct,
+    /// A single key with AltGr, which Windows reports as CTRL and ALT held together.
+    AltGr,
+    /// A dead key followed by the base character.
+    DeadKey,
+    /// No key types the character, so it has to be sent as a `VK_PACKET` Unicode event instead.
+    /// [`crate::to_keystrokes_with_layout`] rejects these characters.
+    UnicodePacket,
+}
+
+/// One character of the text and how it would be typed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CharacterCoverage {
+    /// Byte offset of the character in the text.
+    pub offset: usize,
+    pub character: char,
+    pub strategy: Strategy,
+}
+
+/// How well a layout covers a text, as found by [`CoverageReport::new`].
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct CoverageReport {
+    characters: Vec<CharacterCoverage>,
+}
+
+impl CoverageReport {
+    /// Works out how [`crate::to_keystrokes_with_layout`] would type each character of `text` on
+    /// `layout`, without translating anything.
+    pub fn new(text: &str, layout: &KeyboardLayout) -> CoverageReport {
+        let characters = text.char_indices()
+            .map(|(offset, character)| CharacterCoverage { offset, character, strategy: strategy(character, layout) })
+            .collect();
+        CoverageReport { characters }
+    }
+
+    /// Every character of the text, in order.
+    pub fn characters(&self) -> &[CharacterCoverage] {
+        &self.characters
+    }
+
+    /// How many characters use `strategy`.
+    pub fn count(&self, strategy: Strategy) -> usize {
+        self.characters.iter().filter(|coverage| coverage.strategy == strategy).count()
+    }
+
+    /// Whether the layout can type every character with its keys.
+    pub fn is_complete(&self) -> bool {
+        self.count(Strategy::UnicodePacket) == 0
+    }
+
+    /// The share of characters the layout can type with its keys, from `0.0` to `1.0`. An empty text
+    /// is fully covered.
+    pub fn ratio(&self) -> f64 {
+        if self.characters.is_empty() {
+            return 1.0
+        }
+        (self.characters.len() - self.count(Strategy::UnicodePacket)) as f64 / self.characters.len() as f64
+    }
+
+    /// The distinct characters the layout cannot type, in order of first appearance.
+    pub fn missing(&self) -> Vec<char> {
+        let mut missing: Vec<char> = Vec::new();
+        for coverage in &self.characters {
+            if coverage.strategy == Strategy::UnicodePacket && !missing.contains(&coverage.character) {
+                missing.push(coverage.character);
+            }
+        }
+        missing
+    }
+
+    /// Fails with the same error [`crate::to_keystrokes_with_layout`] would give when a character
+    /// cannot be typed, so input can be rejected before any key is pressed.
+    pub fn check(&self) -> Result<(), KeyError> {
+        match self.characters.iter().find(|coverage| coverage.strategy == Strategy::UnicodePacket) {
+            Some(coverage) => Err(KeyError::not_found(coverage.character)),
+            None => Ok(()),
+        }
+    }
+
+    /// Characters per strategy from the most to the least costly, lower is a better fit.
+    fn cost(&self) -> [usize; 3] {
+        [self.count(Strategy::UnicodePacket), self.count(Strategy::DeadKey), self.count(Strategy::AltGr)]
+    }
+}
+
+fn strategy(character: char, layout: &KeyboardLayout) -> Strategy {
+    match layout.keys_for(character) {
+        Some(KeyPlan::Mapped(mapping)) if mapping.shift_state.contains(ShiftState::CTRL | ShiftState::ALT) => Strategy::AltGr,
+        Some(KeyPlan::Mapped(_)) => Strategy::Direct,
+        Some(KeyPlan::Composed(..)) => Strategy::DeadKey,
+        None => Strategy::UnicodePacket,
+    }
+}
+
+/// Ranks `layouts` by how well they fit `text`, best first: fewest characters that need a Unicode
+/// packet, then fewest dead key compositions, then fewest AltGr characters. Layouts that tie keep
+/// their order.
+pub fn rank_layouts<'a>(text: &str, layouts: impl IntoIterator<Item = &'a KeyboardLayout>) -> Vec<(&'a KeyboardLayout, CoverageReport)> {
+    let mut ranked: Vec<(&KeyboardLayout, CoverageReport)> = layouts.into_iter()
+        .map(|layout| (layout, CoverageReport::new(text, layout)))
+        .collect();
+    ranked.sort_by_key(|(_, report)| report.cost());
+    ranked
+}
+
+
+
+/* ### --- UNIT TEST --- ### */
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorCodes;
+
+    fn strategies(text: &str, layout: &KeyboardLayout) -> Vec<Strategy> {
+        CoverageReport::new(text, layout).characters().iter().map(|coverage| coverage.strategy).collect()
+    }
+
+    #[test]
+    fn test_strategies() {
+        use Strategy::*;
+
+        assert_eq!(strategies("aZ@é✓", KeyboardLayout::german()), [Direct, Direct, AltGr, DeadKey, UnicodePacket]);
+        assert_eq!(strategies("aZ@é✓", KeyboardLayout::us()), [Direct, Direct, Direct, UnicodePacket, UnicodePacket]);
+    }
+
+    #[test]
+    fn test_report() {
+        let report = CoverageReport::new("naïve café ✓ ✓", KeyboardLayout::us());
+
+        assert!(!report.is_complete());
+        assert_eq!(report.missing(), ['ï', 'é', '✓']);
+        assert_eq!(report.count(Strategy::UnicodePacket), 4);
+        assert_eq!(report.characters()[2].offset, 2);
+        assert_eq!(report.characters()[3].offset, 4);
+        assert_eq!(report.ratio(), 10.0 / 14.0);
+    }
+
+    #[test]
+    fn test_check_matches_translator() {
+        for text in ["Grüße", "plain", "dé✓"] {
+            for layout in [KeyboardLayout::us(), KeyboardLayout::german()] {
+                let translated = crate::to_keystrokes_with_layout(text, layout).map(|_| ());
+                assert_eq!(CoverageReport::new(text, layout).check(), translated, "{text:?} on {}", layout.name());
+            }
+        }
+        assert_eq!(CoverageReport::new("ü", KeyboardLayout::us()).check().unwrap_err().error_code, ErrorCodes::NotFound);
+    }
+
+    #[test]
+    fn test_empty_text() {
+        let report = CoverageReport::new("", KeyboardLayout::us());
+
+        assert!(report.is_complete());
+        assert_eq!(report.ratio(), 1.0);
+        assert_eq!(report.check(), Ok(()));
+    }
+
+    #[test]
+    fn test_rank_layouts() {
+        let layouts = [KeyboardLayout::us(), KeyboardLayout::german()];
+
+        let names = |text: &str| -> Vec<String> {
+            rank_layouts(text, layouts).into_iter().map(|(layout, _)| layout.name().to_string()).collect()
+        };
+        assert_eq!(names("Straße"), ["German", "US"]);
+        // German needs AltGr for braces
+        assert_eq!(names("{x}"), ["US", "German"]);
+        assert_eq!(names("plain"), ["US", "German"]);
+    }
+}
@@ -0,0 +1,425 @@
+use std::fmt::Write;
+
+use crate::layout::{to_unicode, KeyboardLayout, ShiftState};
+use crate::scan_code::scan_code;
+use crate::virtual_key::VirtualKey;
+
+/// Where a key sits on a physical keyboard, in units of one key width.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct KeyPosition {
+    /// The virtual key the key produces, under the US layout for [`PhysicalLayout::keys`] and under
+    /// the given layout for [`PhysicalLayout::keys_with_layout`].
+    pub vk: VirtualKey,
+    /// Row counting from the number row at 0 down to the space bar row at 4.
+    pub row: u8,
+    /// Distance of the left edge from the left edge of the board.
+    pub column: f32,
+    pub width: f32,
+}
+
+impl KeyPosition {
+    const fn new(vk: VirtualKey, row: u8, column: f32, width: f32) -> KeyPosition {
+        KeyPosition { vk, row, column, width }
+    }
+
+    /// The right edge.
+    pub fn right(&self) -> f32 {
+        self.column + self.width
+    }
+
+    /// The centre of the key as `(x, y)`, with rows one unit tall.
+    pub fn center(&self) -> (f32, f32) {
+        (self.column + self.width / 2.0, self.row as f32 + 0.5)
+    }
+}
+
+/// Keys in neighbouring rows must overlap by more than this to count as adjacent.
+const MIN_ROW_OVERLAP: f32 = 0.2;
+
+/// Width of one key unit in the ASCII rendering.
+const ASCII_UNIT: f32 = 4.0;
+/// Width of one key unit in the SVG rendering.
+const SVG_UNIT: f32 = 40.0;
+
+/// Builds a board from rows of `KEY @ column, width` entries.
+macro_rules! board {
+    ($($row:literal: [$($vk:ident @ $column:expr, $width:expr);* $(;)?]),* $(,)?) => {
+        &[$($(KeyPosition::new(VirtualKey::$vk, $row, $column, $width),)*)*]
+    };
+}
+
+/// Main block of the 104 key US board.
+static ANSI_KEYS: &[KeyPosition] = board![
+    0: [OEM_3 @ 0.0, 1.0; KEY_1 @ 1.0, 1.0; KEY_2 @ 2.0, 1.0; KEY_3 @ 3.0, 1.0; KEY_4 @ 4.0, 1.0; KEY_5 @ 5.0, 1.0;
+        KEY_6 @ 6.0, 1.0; KEY_7 @ 7.0, 1.0; KEY_8 @ 8.0, 1.0; KEY_9 @ 9.0, 1.0; KEY_0 @ 10.0, 1.0; OEM_MINUS @ 11.0, 1.0;
+        OEM_PLUS @ 12.0, 1.0; BACK @ 13.0, 2.0],
+    1: [TAB @ 0.0, 1.5; KEY_Q @ 1.5, 1.0; KEY_W @ 2.5, 1.0; KEY_E @ 3.5, 1.0; KEY_R @ 4.5, 1.0; KEY_T @ 5.5, 1.0;
+        KEY_Y @ 6.5, 1.0; KEY_U @ 7.5, 1.0; KEY_I @ 8.5, 1.0; KEY_O @ 9.5, 1.0; KEY_P @ 10.5, 1.0; OEM_4 @ 11.5, 1.0;
+        OEM_6 @ 12.5, 1.0; OEM_5 @ 13.5, 1.5],
+    2: [CAPITAL @ 0.0, 1.75; KEY_A @ 1.75, 1.0; KEY_S @ 2.75, 1.0; KEY_D @ 3.75, 1.0; KEY_F @ 4.75, 1.0;
+        KEY_G @ 5.75, 1.0; KEY_H @ 6.75, 1.0; KEY_J @ 7.75, 1.0; KEY_K @ 8.75, 1.0; KEY_L @ 9.75, 1.0; OEM_1 @ 10.75, 1.0;
+        OEM_7 @ 11.75, 1.0; RETURN @ 12.75, 2.25],
+    3: [LSHIFT @ 0.0, 2.25; KEY_Z @ 2.25, 1.0; KEY_X @ 3.25, 1.0; KEY_C @ 4.25, 1.0; KEY_V @ 5.25, 1.0;
+        KEY_B @ 6.25, 1.0; KEY_N @ 7.25, 1.0; KEY_M @ 8.25, 1.0; OEM_COMMA @ 9.25, 1.0; OEM_PERIOD @ 10.25, 1.0;
+        OEM_2 @ 11.25, 1.0; RSHIFT @ 12.25, 2.75],
+    4: [LCONTROL @ 0.0, 1.25; LWIN @ 1.25, 1.25; LMENU @ 2.5, 1.25; SPACE @ 3.75, 6.25; RMENU @ 10.0, 1.25;
+        RWIN @ 11.25, 1.25; APPS @ 12.5, 1.25; RCONTROL @ 13.75, 1.25],
+];
+
+/// Main block of the 105 key ISO board. The top of the L shaped enter key is used as its position,
+/// the key left of it is [`VirtualKey::OEM_5`] and the extra key left of Z is [`VirtualKey::OEM_102`].
+static ISO_KEYS: &[KeyPosition] = board![
+    0: [OEM_3 @ 0.0, 1.0; KEY_1 @ 1.0, 1.0; KEY_2 @ 2.0, 1.0; KEY_3 @ 3.0, 1.0; KEY_4 @ 4.0, 1.0; KEY_5 @ 5.0, 1.0;
+        KEY_6 @ 6.0, 1.0; KEY_7 @ 7.0, 1.0; KEY_8 @ 8.0, 1.0; KEY_9 @ 9.0, 1.0; KEY_0 @ 10.0, 1.0; OEM_MINUS @ 11.0, 1.0;
+        OEM_PLUS @ 12.0, 1.0; BACK @ 13.0, 2.0],
+    1: [TAB @ 0.0, 1.5; KEY_Q @ 1.5, 1.0; KEY_W @ 2.5, 1.0; KEY_E @ 3.5, 1.0; KEY_R @ 4.5, 1.0; KEY_T @ 5.5, 1.0;
+        KEY_Y @ 6.5, 1.0; KEY_U @ 7.5, 1.0; KEY_I @ 8.5, 1.0; KEY_O @ 9.5, 1.0; KEY_P @ 10.5, 1.0; OEM_4 @ 11.5, 1.0;
+        OEM_6 @ 12.5, 1.0; RETURN @ 13.5, 1.5],
+    2: [CAPITAL @ 0.0, 1.75; KEY_A @ 1.75, 1.0; KEY_S @ 2.75, 1.0; KEY_D @ 3.75, 1.0; KEY_F @ 4.75, 1.0;
+        KEY_G @ 5.75, 1.0; KEY_H @ 6.75, 1.0; KEY_J @ 7.75, 1.0; KEY_K @ 8.75, 1.0; KEY_L @ 9.75, 1.0; OEM_1 @ 10.75, 1.0;
+        OEM_7 @ 11.75, 1.0; OEM_5 @ 12.75, 1.0],
+    3: [LSHIFT @ 0.0, 1.25; OEM_102 @ 1.25, 1.0; KEY_Z @ 2.25, 1.0; KEY_X @ 3.25, 1.0; KEY_C @ 4.25, 1.0;
+        KEY_V @ 5.25, 1.0; KEY_B @ 6.25, 1.0; KEY_N @ 7.25, 1.0; KEY_M @ 8.25, 1.0; OEM_COMMA @ 9.25, 1.0;
+        OEM_PERIOD @ 10.25, 1.0; OEM_2 @ 11.25, 1.0; RSHIFT @ 12.25, 2.75],
+    4: [LCONTROL @ 0.0, 1.25; LWIN @ 1.25, 1.25; LMENU @ 2.5, 1.25; SPACE @ 3.75, 6.25; RMENU @ 10.0, 1.25;
+        RWIN @ 11.25, 1.25; APPS @ 12.5, 1.25; RCONTROL @ 13.75, 1.25],
+];
+
+/// Main block of the 109 key JIS board, with the keys the Japanese layout assigns to them. The top of
+/// the enter key is used as its position.
+static JIS_KEYS: &[KeyPosition] = board![
+    0: [KANJI @ 0.0, 1.0; KEY_1 @ 1.0, 1.0; KEY_2 @ 2.0, 1.0; KEY_3 @ 3.0, 1.0; KEY_4 @ 4.0, 1.0; KEY_5 @ 5.0, 1.0;
+        KEY_6 @ 6.0, 1.0; KEY_7 @ 7.0, 1.0; KEY_8 @ 8.0, 1.0; KEY_9 @ 9.0, 1.0; KEY_0 @ 10.0, 1.0; OEM_MINUS @ 11.0, 1.0;
+        OEM_7 @ 12.0, 1.0; OEM_5 @ 13.0, 1.0; BACK @ 14.0, 1.0],
+    1: [TAB @ 0.0, 1.5; KEY_Q @ 1.5, 1.0; KEY_W @ 2.5, 1.0; KEY_E @ 3.5, 1.0; KEY_R @ 4.5, 1.0; KEY_T @ 5.5, 1.0;
+        KEY_Y @ 6.5, 1.0; KEY_U @ 7.5, 1.0; KEY_I @ 8.5, 1.0; KEY_O @ 9.5, 1.0; KEY_P @ 10.5, 1.0; OEM_3 @ 11.5, 1.0;
+        OEM_4 @ 12.5, 1.0; RETURN @ 13.5, 1.5],
+    2: [CAPITAL @ 0.0, 1.75; KEY_A @ 1.75, 1.0; KEY_S @ 2.75, 1.0; KEY_D @ 3.75, 1.0; KEY_F @ 4.75, 1.0;
+        KEY_G @ 5.75, 1.0; KEY_H @ 6.75, 1.0; KEY_J @ 7.75, 1.0; KEY_K @ 8.75, 1.0; KEY_L @ 9.75, 1.0;
+        OEM_PLUS @ 10.75, 1.0; OEM_1 @ 11.75, 1.0; OEM_6 @ 12.75, 1.0],
+    3: [LSHIFT @ 0.0, 2.25; KEY_Z @ 2.25, 1.0; KEY_X @ 3.25, 1.0; KEY_C @ 4.25, 1.0; KEY_V @ 5.25, 1.0;
+        KEY_B @ 6.25, 1.0; KEY_N @ 7.25, 1.0; KEY_M @ 8.25, 1.0; OEM_COMMA @ 9.25, 1.0; OEM_PERIOD @ 10.25, 1.0;
+        OEM_2 @ 11.25, 1.0; OEM_102 @ 12.25, 1.0; RSHIFT @ 13.25, 1.75],
+    4: [LCONTROL @ 0.0, 1.25; LWIN @ 1.25, 1.25; LMENU @ 2.5, 1.25; NONCONVERT @ 3.75, 1.25; SPACE @ 5.0, 3.5;
+        CONVERT @ 8.5, 1.25; KANA @ 9.75, 1.25; RMENU @ 11.0, 1.25; APPS @ 12.25, 1.25; RCONTROL @ 13.5, 1.5],
+];
+
+/// The physical shape of a keyboard. Boards differ in the size and number of keys around enter and
+/// the left shift key, and JIS adds IME keys around the space bar.
+///
+/// Methods without a layout address keys the way the US layout does. The `_with_layout` variants
+/// find each key through its scan code instead, so on German `Z` sits where US has `Y`. The JIS board
+/// is already laid out for the Japanese layout and is never rearranged.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum PhysicalLayout {
+    /// The US 104 key board.
+    Ansi,
+    /// The European 105 key board.
+    Iso,
+    /// The Japanese 109 key board.
+    Jis,
+}
+
+impl PhysicalLayout {
+    /// Every key in the main block, row by row from left to right.
+    pub fn keys(self) -> &'static [KeyPosition] {
+        match self {
+            PhysicalLayout::Ansi => ANSI_KEYS,
+            PhysicalLayout::Iso => ISO_KEYS,
+            PhysicalLayout::Jis => JIS_KEYS,
+        }
+    }
+
+    /// Every key in the main block like [`PhysicalLayout::keys`], each with the virtual key `layout`
+    /// produces at its scan code.
+    pub fn keys_with_layout(self, layout: &KeyboardLayout) -> Vec<KeyPosition> {
+        self.keys().iter()
+            .map(|&key| match self {
+                PhysicalLayout::Jis => key,
+                PhysicalLayout::Ansi | PhysicalLayout::Iso => KeyPosition {
+                    vk: scan_code(key.vk).and_then(|scan| layout.key_for_scan_code(scan)).unwrap_or(key.vk),
+                    ..key
+                },
+            })
+            .collect()
+    }
+
+    /// Where `vk` is on the board. Generic modifiers are found at their left hand key.
+    pub fn position(self, vk: VirtualKey) -> Option<KeyPosition> {
+        find(self.keys(), vk)
+    }
+
+    /// Where the key producing `vk` on `layout` is on the board.
+    pub fn position_with_layout(self, vk: VirtualKey, layout: &KeyboardLayout) -> Option<KeyPosition> {
+        find(&self.keys_with_layout(layout), vk)
+    }
+
+    /// The distance between the centres of two keys in key widths.
+    pub fn distance(self, from: VirtualKey, to: VirtualKey) -> Option<f32> {
+        let (x1, y1) = self.position(from)?.center();
+        let (x2, y2) = self.position(to)?.center();
+        Some((x2 - x1).hypot(y2 - y1))
+    }
+
+    /// Keys physically next to `vk`, including diagonal neighbours.
+    pub fn adjacent_keys(self, vk: VirtualKey) -> Vec<VirtualKey> {
+        adjacent(self.keys(), vk)
+    }
+
+    /// Keys physically next to the key producing `vk` on `layout`, as `layout` addresses them.
+    pub fn adjacent_keys_with_layout(self, vk: VirtualKey, layout: &KeyboardLayout) -> Vec<VirtualKey> {
+        adjacent(&self.keys_with_layout(layout), vk)
+    }
+
+    /// Draws the board as text, one line per row. Keys are placed by scan code and show what they
+    /// type on `layout`, and the `highlighted` keys, as `layout` addresses them, are drawn with `<` `>`
+    /// instead of `[` `]`.
+    pub fn render_ascii(self, layout: &KeyboardLayout, highlighted: &[VirtualKey]) -> String {
+        let keys = self.keys_with_layout(layout);
+        let mut text = String::new();
+        for row in 0..5 {
+            let mut line = String::new();
+            for key in keys.iter().filter(|key| key.row == row) {
+                let start = (key.column * ASCII_UNIT).round() as usize;
+                let width = (key.width * ASCII_UNIT).round() as usize;
+                let (open, close) = if is_highlighted(key.vk, highlighted) { ('<', '>') } else { ('[', ']') };
+                let label: String = label(key.vk, layout).chars().take(width - 2).collect();
+
+                while line.chars().count() < start {
+                    line.push(' ');
+                }
+                let _ = write!(line, "{open}{label:<0$}{close}", width - 2);
+            }
+            text.push_str(line.trim_end());
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Draws the board as an SVG image, placing and highlighting keys like
+    /// [`PhysicalLayout::render_ascii`]. The `highlighted` keys are filled.
+    pub fn render_svg(self, layout: &KeyboardLayout, highlighted: &[VirtualKey]) -> String {
+        let keys = self.keys_with_layout(layout);
+        let width = keys.iter().map(KeyPosition::right).fold(0.0, f32::max) * SVG_UNIT;
+        let height = 5.0 * SVG_UNIT;
+
+        let mut svg = String::new();
+        let _ = writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {width} {height}" font-family="sans-serif" font-size="12">"#);
+        for key in &keys {
+            let fill = if is_highlighted(key.vk, highlighted) { "#f5b942" } else { "#eeeeee" };
+            let (x, y) = (key.column * SVG_UNIT, key.row as f32 * SVG_UNIT);
+            let _ = writeln!(svg,
+                r##"<rect x="{}" y="{}" width="{}" height="{}" rx="4" fill="{fill}" stroke="#555555"/>"##,
+                x + 1.0, y + 1.0, key.width * SVG_UNIT - 2.0, SVG_UNIT - 2.0);
+            let _ = writeln!(svg,
+                r#"<text x="{}" y="{}" text-anchor="middle" dominant-baseline="central">{}</text>"#,
+                x + key.width * SVG_UNIT / 2.0, y + SVG_UNIT / 2.0, escape_xml(&label(key.vk, layout)));
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+fn find(keys: &[KeyPosition], vk: VirtualKey) -> Option<KeyPosition> {
+    let vk = left_hand(vk);
+    keys.iter().find(|key| key.vk == vk).copied()
+}
+
+fn adjacent(keys: &[KeyPosition], vk: VirtualKey) -> Vec<VirtualKey> {
+    let Some(key) = find(keys, vk) else {
+        return Vec::new()
+    };
+
+    keys.iter()
+        .filter(|other| match key.row.abs_diff(other.row) {
+            0 => other.vk != key.vk && (other.right() == key.column || other.column == key.right()),
+            1 => key.right().min(other.right()) - key.column.max(other.column) > MIN_ROW_OVERLAP,
+            _ => false,
+        })
+        .map(|other| other.vk)
+        .collect()
+}
+
+/// Generic modifiers are drawn at their left hand key.
+fn left_hand(vk: VirtualKey) -> VirtualKey {
+    match vk {
+        VirtualKey::SHIFT => VirtualKey::LSHIFT,
+        VirtualKey::CONTROL => VirtualKey::LCONTROL,
+        VirtualKey::MENU => VirtualKey::LMENU,
+        _ => vk,
+    }
+}
+
+fn is_highlighted(vk: VirtualKey, highlighted: &[VirtualKey]) -> bool {
+    highlighted.iter().any(|&other| left_hand(other) == vk)
+}
+
+/// A short name for a key, the uppercase character it types when it types one, falling back to a
+/// character it types with modifiers.
+fn label(vk: VirtualKey, layout: &KeyboardLayout) -> String {
+    let name = match vk {
+        VirtualKey::BACK => "Bksp",
+        VirtualKey::TAB => "Tab",
+        VirtualKey::CAPITAL => "Caps",
+        VirtualKey::RETURN => "Enter",
+        VirtualKey::LSHIFT | VirtualKey::RSHIFT => "Shift",
+        VirtualKey::LCONTROL | VirtualKey::RCONTROL => "Ctrl",
+        VirtualKey::LWIN | VirtualKey::RWIN => "Win",
+        VirtualKey::LMENU | VirtualKey::RMENU => "Alt",
+        VirtualKey::APPS => "Menu",
+        VirtualKey::SPACE => "",
+        // Keys that only type something with modifiers, like the US - key whose - is on the numpad,
+        // are named after whatever they type
+        _ => match to_unicode(vk, ShiftState::NONE, layout)
+            .or_else(|| layout.mappings().iter().find(|mapping| mapping.vk == vk).map(|mapping| mapping.character))
+        {
+            // Keep characters like ß that have no single uppercase letter
+            Some(character) => {
+                let upper = character.to_uppercase();
+                return if upper.len() == 1 { upper.collect() } else { character.to_string() }
+            }
+            // Punctuation keys the layout does not use are left blank rather than named
+            None if vk.name().is_some_and(|name| name.starts_with("VK_OEM_")) => "",
+            None => return vk.name().map_or_else(|| vk.to_string(), |name| name[3..].to_string()),
+        },
+    };
+    name.to_string()
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+
+
+/* ### --- UNIT TEST --- ### */
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOARDS: [PhysicalLayout; 3] = [PhysicalLayout::Ansi, PhysicalLayout::Iso, PhysicalLayout::Jis];
+
+    #[test]
+    fn test_rows_do_not_overlap() {
+        for board in BOARDS {
+            for row in 0..5 {
+                let keys: Vec<&KeyPosition> = board.keys().iter().filter(|key| key.row == row).collect();
+                assert_eq!(keys[0].column, 0.0);
+                for pair in keys.windows(2) {
+                    assert_eq!(pair[0].right(), pair[1].column, "{board:?} row {row}");
+                }
+                // The bottom of the enter key fills the end of the home row on ISO and JIS boards
+                let end = if row == 2 && board != PhysicalLayout::Ansi { 13.75 } else { 15.0 };
+                assert_eq!(keys.last().unwrap().right(), end, "{board:?} row {row}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_every_us_character_has_a_position() {
+        for mapping in KeyboardLayout::us().mappings() {
+            for board in [PhysicalLayout::Ansi, PhysicalLayout::Iso] {
+                // The US translator types a few symbols on the numpad
+                if !matches!(mapping.vk, VirtualKey::MULTIPLY | VirtualKey::SUBTRACT | VirtualKey::DIVIDE) {
+                    assert!(board.position(mapping.vk).is_some(), "{board:?} {:?}", mapping.character);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_board_differences() {
+        assert!(PhysicalLayout::Ansi.position(VirtualKey::OEM_102).is_none());
+        assert_eq!(PhysicalLayout::Iso.position(VirtualKey::OEM_102).unwrap().row, 3);
+        assert_eq!(PhysicalLayout::Ansi.position(VirtualKey::OEM_5).unwrap().row, 1);
+        assert_eq!(PhysicalLayout::Iso.position(VirtualKey::OEM_5).unwrap().row, 2);
+        assert!(PhysicalLayout::Jis.position(VirtualKey::CONVERT).is_some());
+        assert_eq!(PhysicalLayout::Ansi.position(VirtualKey::SHIFT), PhysicalLayout::Ansi.position(VirtualKey::LSHIFT));
+    }
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(PhysicalLayout::Ansi.distance(VirtualKey::KEY_F, VirtualKey::KEY_J), Some(3.0));
+        assert_eq!(PhysicalLayout::Ansi.distance(VirtualKey::KEY_A, VirtualKey::KEY_Q), Some(0.25f32.hypot(1.0)));
+        assert_eq!(PhysicalLayout::Ansi.distance(VirtualKey::KEY_A, VirtualKey::F1), None);
+    }
+
+    #[test]
+    fn test_adjacent_keys() {
+        let mut keys = PhysicalLayout::Iso.adjacent_keys(VirtualKey::KEY_Z);
+        keys.sort();
+
+        assert_eq!(keys, vec![
+            VirtualKey::KEY_A, VirtualKey::KEY_S, VirtualKey::KEY_X, VirtualKey::LWIN, VirtualKey::LMENU, VirtualKey::OEM_102,
+        ]);
+    }
+
+    #[test]
+    fn test_render_ascii() {
+        let highlighted: Vec<VirtualKey> = crate::decode_keystrokes(&crate::to_keystrokes_new("Hi").unwrap())
+            .into_iter()
+            .map(|keystroke| keystroke.vk())
+            .collect();
+        let text = PhysicalLayout::Ansi.render_ascii(KeyboardLayout::us(), &highlighted);
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], "[` ][1 ][2 ][3 ][4 ][5 ][6 ][7 ][8 ][9 ][0 ][_ ][= ][Bksp  ]");
+        assert!(lines[1].contains("<I >"));
+        assert!(lines[2].contains("<H >") && lines[2].contains("[J ]"));
+        assert!(lines[3].starts_with("<Shift  >"));
+    }
+
+    #[test]
+    fn test_keys_follow_scan_codes() {
+        let german = KeyboardLayout::german();
+
+        let z = PhysicalLayout::Iso.position_with_layout(VirtualKey::KEY_Z, german).unwrap();
+        assert_eq!((z.row, z.column), (1, 6.5));
+        let sharp_s = PhysicalLayout::Iso.position_with_layout(VirtualKey::OEM_4, german).unwrap();
+        assert_eq!((sharp_s.row, sharp_s.column), (0, 11.0));
+        assert_eq!(PhysicalLayout::Iso.position_with_layout(VirtualKey::KEY_Y, german).unwrap().row, 3);
+
+        let mut keys = PhysicalLayout::Iso.adjacent_keys_with_layout(VirtualKey::KEY_Y, german);
+        keys.sort();
+        assert_eq!(keys, vec![
+            VirtualKey::KEY_A, VirtualKey::KEY_S, VirtualKey::KEY_X, VirtualKey::LWIN, VirtualKey::LMENU, VirtualKey::OEM_102,
+        ]);
+        assert_eq!(PhysicalLayout::Jis.keys_with_layout(german), PhysicalLayout::Jis.keys());
+    }
+
+    #[test]
+    fn test_render_german() {
+        let text = PhysicalLayout::Iso.render_ascii(KeyboardLayout::german(), &[VirtualKey::KEY_Z]);
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert!(lines[0].contains("[0 ][ß ]"));
+        assert!(lines[1].contains("[T ]<Z >[U ]"));
+        assert!(lines[1].contains("[P ][Ü ][+ ]"));
+        assert!(lines[3].contains("[< ][Y ][X ]"));
+    }
+
+    #[test]
+    fn test_render_svg() {
+        let svg = PhysicalLayout::Jis.render_svg(KeyboardLayout::us(), &[VirtualKey::KEY_A]);
+
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert_eq!(svg.matches("<rect").count(), PhysicalLayout::Jis.keys().len());
+        assert_eq!(svg.matches("#f5b942").count(), 1);
+        assert!(!svg.contains(">&<"));
+    }
+}
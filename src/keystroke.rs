@@ -0,0 +1,198 @@
+use crate::layout::ShiftState;
+use crate::virtual_key::VirtualKey;
+
+/// A single key event.
+///
+/// The translation functions return a flat `Vec<u8>` where modifier keys toggle between pressed and
+/// released and every other key is tapped. [`decode_keystrokes`] turns that into explicit events.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Keystroke {
+    /// The key is pressed and held.
+    Down(VirtualKey),
+    /// The key is released.
+    Up(VirtualKey),
+    /// The key is pressed and immediately released.
+    Tap(VirtualKey),
+    /// A key that is already down is pressed again, the way typematic auto-repeat reports a held key.
+    /// Windows sends it as a `WM_KEYDOWN` with the previous key state bit (bit 30 of `lParam`) set.
+    Repeat(VirtualKey),
+}
+
+impl Keystroke {
+    /// The key this event applies to.
+    pub fn vk(self) -> VirtualKey {
+        match self {
+            Keystroke::Down(vk) | Keystroke::Up(vk) | Keystroke::Tap(vk) | Keystroke::Repeat(vk) => vk,
+        }
+    }
+}
+
+/// Whether `vk` is a modifier, meaning it is held down across other keys rather than tapped. In the
+/// flat `Vec<u8>` format every occurrence of a modifier toggles it between pressed and released.
+pub fn is_modifier(vk: VirtualKey) -> bool {
+    matches!(vk,
+        VirtualKey::SHIFT | VirtualKey::LSHIFT | VirtualKey::RSHIFT |
+        VirtualKey::CONTROL | VirtualKey::LCONTROL | VirtualKey::RCONTROL |
+        VirtualKey::MENU | VirtualKey::LMENU | VirtualKey::RMENU |
+        VirtualKey::LWIN | VirtualKey::RWIN)
+}
+
+/// Decodes the flat output of [`crate::to_keystrokes_new`] into explicit key events. Modifiers
+/// alternate between [`Keystroke::Down`] and [`Keystroke::Up`], every other key becomes a
+/// [`Keystroke::Tap`].
+pub fn decode_keystrokes(keystrokes: &[u8]) -> Vec<Keystroke> {
+    let mut held = [false; 256];
+    keystrokes.iter()
+        .map(|&code| {
+            let vk = VirtualKey(code);
+            if !is_modifier(vk) {
+                return Keystroke::Tap(vk)
+            }
+            let is_held = &mut held[code as usize];
+            *is_held = !*is_held;
+            if *is_held { Keystroke::Down(vk) } else { Keystroke::Up(vk) }
+        })
+        .collect()
+}
+
+
+/// Writes keys into the flat `Vec<u8>` format, pressing and releasing modifiers only when the shift
+/// state changes, the same way [`crate::to_keystrokes_mut`] coalesces shift runs.
+pub(crate) struct ModifierEncoder<'a> {
+    keystrokes: &'a mut Vec<u8>,
+    held: ShiftState,
+}
+
+impl<'a> ModifierEncoder<'a> {
+    /// Modifier keys in the order they are pressed, they are released in reverse.
+    const MODIFIERS: [(ShiftState, VirtualKey); 3] = [
+        (ShiftState::CTRL, VirtualKey::CONTROL),
+        (ShiftState::ALT, VirtualKey::MENU),
+        (ShiftState::SHIFT, VirtualKey::SHIFT),
+    ];
+
+    pub(crate) fn new(keystrokes: &'a mut Vec<u8>) -> ModifierEncoder<'a> {
+        ModifierEncoder { keystrokes, held: ShiftState::NONE }
+    }
+
+    /// Pushes `vk`, first releasing and pressing modifiers so exactly `shift_state` is held. Flags
+    /// without a modifier key, such as [`ShiftState::HANKAKU`], are ignored.
+    pub(crate) fn push(&mut self, vk: VirtualKey, shift_state: ShiftState) {
+        self.release(shift_state);
+        self.press(shift_state);
+        self.keystrokes.push(vk.0);
+    }
+
+    /// Releases every held modifier.
+    pub(crate) fn finish(mut self) {
+        self.release(ShiftState::NONE);
+    }
+
+    /// Releases held modifiers that are not part of `shift_state`, returning how many were released.
+    pub(crate) fn release(&mut self, shift_state: ShiftState) -> usize {
+        let mut count = 0;
+        for &(flag, vk) in Self::MODIFIERS.iter().rev() {
+            if self.held.contains(flag) && !shift_state.contains(flag) {
+                self.keystrokes.push(vk.0);
+                self.held = self.held.difference(flag);
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Presses the modifiers in `shift_state` that are not held yet, returning how many were pressed.
+    pub(crate) fn press(&mut self, shift_state: ShiftState) -> usize {
+        let mut count = 0;
+        for &(flag, vk) in Self::MODIFIERS.iter() {
+            if !self.held.contains(flag) && shift_state.contains(flag) {
+                self.keystrokes.push(vk.0);
+                self.held |= flag;
+                count += 1;
+            }
+        }
+        count
+    }
+}
+
+
+
+/* ### --- UNIT TEST --- ### */
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_lowercase() {
+        let events = decode_keystrokes(&crate::to_keystrokes_new("hi").unwrap());
+
+        assert_eq!(events, vec![Keystroke::Tap(VirtualKey::KEY_H), Keystroke::Tap(VirtualKey::KEY_I)]);
+    }
+
+    #[test]
+    fn test_decode_shift_toggles() {
+        let events = decode_keystrokes(&crate::to_keystrokes_new("aBCd").unwrap());
+
+        assert_eq!(events, vec![
+            Keystroke::Tap(VirtualKey::KEY_A),
+            Keystroke::Down(VirtualKey::SHIFT),
+            Keystroke::Tap(VirtualKey::KEY_B),
+            Keystroke::Tap(VirtualKey::KEY_C),
+            Keystroke::Up(VirtualKey::SHIFT),
+            Keystroke::Tap(VirtualKey::KEY_D),
+        ]);
+    }
+
+    #[test]
+    fn test_modifiers_toggle_independently() {
+        let events = decode_keystrokes(&[0x11, 0x12, 0x45, 0x11, 0x12]);
+
+        assert_eq!(events, vec![
+            Keystroke::Down(VirtualKey::CONTROL),
+            Keystroke::Down(VirtualKey::MENU),
+            Keystroke::Tap(VirtualKey::KEY_E),
+            Keystroke::Up(VirtualKey::CONTROL),
+            Keystroke::Up(VirtualKey::MENU),
+        ]);
+    }
+
+    #[test]
+    fn test_encoder_matches_translator() {
+        let text = "ZbcAzCaZc Hello, World! {x} ~";
+        let mut keystrokes = Vec::new();
+        let mut encoder = ModifierEncoder::new(&mut keystrokes);
+        for character in text.chars() {
+            let (vk, shift_state) = crate::vk_key_scan(character, crate::KeyboardLayout::us()).unwrap();
+            encoder.push(vk, shift_state);
+        }
+        encoder.finish();
+
+        assert_eq!(keystrokes, crate::to_keystrokes_new(text).unwrap());
+    }
+
+    #[test]
+    fn test_encoder_ctrl_alt() {
+        let mut keystrokes = Vec::new();
+        let mut encoder = ModifierEncoder::new(&mut keystrokes);
+        encoder.push(VirtualKey::KEY_Q, ShiftState::CTRL | ShiftState::ALT);
+        encoder.push(VirtualKey::KEY_E, ShiftState::CTRL | ShiftState::ALT | ShiftState::SHIFT);
+        encoder.push(VirtualKey::KEY_A, ShiftState::NONE);
+        encoder.finish();
+
+        assert_eq!(keystrokes, vec![0x11, 0x12, 0x51, 0x10, 0x45, 0x10, 0x12, 0x11, 0x41]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let events = decode_keystrokes(&crate::to_keystrokes_new("Hi").unwrap());
+        let json = serde_json::to_string(&events).unwrap();
+
+        assert_eq!(json, r#"[{"Down":"VK_SHIFT"},{"Tap":"VK_H"},{"Up":"VK_SHIFT"},{"Tap":"VK_I"}]"#);
+        assert_eq!(serde_json::from_str::<Vec<Keystroke>>(&json).unwrap(), events);
+    }
+}
@@ -0,0 +1,676 @@
+use std::collections::HashMap;
+use std::ops::{BitOr, BitOrAssign};
+use std::sync::OnceLock;
+
+use crate::keystroke::ModifierEncoder;
+use crate::scan_code::{scan_code, virtual_key};
+use crate::virtual_key::VirtualKey;
+use crate::{ascii, KeyError, CHAR_TO_KEY_MAP, ASCII_LOWERCASE_TO_UPPERCASE_OFFSET_AND_ASCII_MIN_VALUE};
+
+/// Modifier flags stored in the high byte of a `VkKeyScanExW` result.
+/// See: https://learn.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-vkkeyscanexw#return-value
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct ShiftState(u8);
+
+impl ShiftState {
+    /// No modifier is required.
+    pub const NONE: ShiftState = ShiftState(0);
+    /// Either shift key is pressed.
+    pub const SHIFT: ShiftState = ShiftState(0x01);
+    /// Either CTRL key is pressed.
+    pub const CTRL: ShiftState = ShiftState(0x02);
+    /// Either ALT key is pressed.
+    pub const ALT: ShiftState = ShiftState(0x04);
+    /// The Hankaku key is pressed.
+    pub const HANKAKU: ShiftState = ShiftState(0x08);
+
+    /// Creates a shift state from the raw high byte of a `VkKeyScanExW` result. Bits 4 and 5 are
+    /// reserved by Windows for the keyboard layout driver and are kept as is.
+    pub const fn from_bits(bits: u8) -> ShiftState {
+        ShiftState(bits)
+    }
+
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether every flag set in `other` is also set in `self`.
+    pub const fn contains(self, other: ShiftState) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn union(self, other: ShiftState) -> ShiftState {
+        ShiftState(self.0 | other.0)
+    }
+
+    /// The flags set in `self` but not in `other`.
+    pub const fn difference(self, other: ShiftState) -> ShiftState {
+        ShiftState(self.0 & !other.0)
+    }
+
+    /// The flag a modifier key contributes while held, or [`ShiftState::NONE`] for other keys.
+    pub fn for_modifier(vk: VirtualKey) -> ShiftState {
+        match vk {
+            VirtualKey::SHIFT | VirtualKey::LSHIFT | VirtualKey::RSHIFT => ShiftState::SHIFT,
+            VirtualKey::CONTROL | VirtualKey::LCONTROL | VirtualKey::RCONTROL => ShiftState::CTRL,
+            VirtualKey::MENU | VirtualKey::LMENU | VirtualKey::RMENU => ShiftState::ALT,
+            _ => ShiftState::NONE,
+        }
+    }
+}
+
+impl BitOr for ShiftState {
+    type Output = ShiftState;
+
+    fn bitor(self, rhs: ShiftState) -> ShiftState {
+        self.union(rhs)
+    }
+}
+
+impl BitOrAssign for ShiftState {
+    fn bitor_assign(&mut self, rhs: ShiftState) {
+        self.0 |= rhs.0;
+    }
+}
+
+#[cfg(feature = "serde")]
+const SHIFT_STATE_NAMES: [(ShiftState, &str); 4] = [
+    (ShiftState::SHIFT, "SHIFT"),
+    (ShiftState::CTRL, "CTRL"),
+    (ShiftState::ALT, "ALT"),
+    (ShiftState::HANKAKU, "HANKAKU"),
+];
+
+/// Serializes as a list of flag names such as `["CTRL", "ALT"]`, falling back to the raw bits when a
+/// reserved bit is set.
+#[cfg(feature = "serde")]
+impl serde::Serialize for ShiftState {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeSeq;
+
+        let named = SHIFT_STATE_NAMES.iter().fold(0, |bits, (flag, _)| bits | flag.0);
+        if self.0 & !named != 0 {
+            return serializer.serialize_u8(self.0)
+        }
+
+        let flags: Vec<&str> = SHIFT_STATE_NAMES.iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|&(_, name)| name)
+            .collect();
+        let mut seq = serializer.serialize_seq(Some(flags.len()))?;
+        for name in flags {
+            seq.serialize_element(name)?;
+        }
+        seq.end()
+    }
+}
+
+/// Accepts a list of flag names or the raw bits.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ShiftState {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use std::fmt;
+
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = ShiftState;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a list of shift state flags or their bits")
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, bits: u64) -> Result<ShiftState, E> {
+                u8::try_from(bits)
+                    .map(ShiftState)
+                    .map_err(|_| E::invalid_value(serde::de::Unexpected::Unsigned(bits), &self))
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<ShiftState, A::Error> {
+                let mut shift_state = ShiftState::NONE;
+                while let Some(name) = seq.next_element::<String>()? {
+                    let &(flag, _) = SHIFT_STATE_NAMES.iter()
+                        .find(|(_, known)| known.eq_ignore_ascii_case(&name))
+                        .ok_or_else(|| serde::de::Error::unknown_variant(&name, &["SHIFT", "CTRL", "ALT", "HANKAKU"]))?;
+                    shift_state |= flag;
+                }
+                Ok(shift_state)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+/// A single character a layout can produce along with the key and modifiers that produce it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KeyMapping {
+    pub character: char,
+    pub vk: VirtualKey,
+    pub shift_state: ShiftState,
+}
+
+/// A key that types nothing by itself but changes the next character, like `´` followed by `e` typing
+/// `é`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeadKey {
+    pub vk: VirtualKey,
+    pub shift_state: ShiftState,
+    /// Pairs of the character typed after the dead key and the character that results, e.g.
+    /// `('e', 'é')`. Following a dead key with space usually types the accent on its own.
+    pub compositions: Vec<(char, char)>,
+}
+
+/// The keys that type a character, as found by [`KeyboardLayout::keys_for`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum KeyPlan<'a> {
+    /// A single key, possibly with modifiers.
+    Mapped(&'a KeyMapping),
+    /// A dead key followed by the key of the base character.
+    Composed(&'a DeadKey, &'a KeyMapping),
+}
+
+impl<'a> KeyPlan<'a> {
+    /// The keys to press in order, each with the modifiers it needs.
+    pub fn keys(self) -> impl Iterator<Item = (VirtualKey, ShiftState)> + 'a {
+        let (dead_key, mapping) = match self {
+            KeyPlan::Mapped(mapping) => (None, mapping),
+            KeyPlan::Composed(dead_key, mapping) => (Some(dead_key), mapping),
+        };
+        dead_key.map(|dead_key| (dead_key.vk, dead_key.shift_state)).into_iter()
+            .chain([(mapping.vk, mapping.shift_state)])
+    }
+}
+
+/// The set of characters a keyboard layout can type.
+///
+/// When several mappings produce the same character the first one wins, mirroring how `VkKeyScanExW`
+/// reports only one key per character. The same applies to several characters sharing one key and
+/// shift state in the reverse direction.
+#[derive(Clone, Debug)]
+pub struct KeyboardLayout {
+    name: String,
+    mappings: Vec<KeyMapping>,
+    by_char: HashMap<char, usize>,
+    by_key: HashMap<(VirtualKey, ShiftState), usize>,
+    dead_keys: Vec<DeadKey>,
+    scan_codes: Vec<(u16, VirtualKey)>,
+}
+
+impl KeyboardLayout {
+    pub fn new(name: impl Into<String>, mappings: Vec<KeyMapping>) -> KeyboardLayout {
+        let mut by_char = HashMap::with_capacity(mappings.len());
+        let mut by_key = HashMap::with_capacity(mappings.len());
+        for (i, mapping) in mappings.iter().enumerate() {
+            by_char.entry(mapping.character).or_insert(i);
+            by_key.entry((mapping.vk, mapping.shift_state)).or_insert(i);
+        }
+        KeyboardLayout { name: name.into(), mappings, by_char, by_key, dead_keys: Vec::new(), scan_codes: Vec::new() }
+    }
+
+    /// Adds dead keys, which [`KeyboardLayout::keys_for`] uses for characters no single key types.
+    pub fn with_dead_keys(mut self, dead_keys: Vec<DeadKey>) -> KeyboardLayout {
+        self.dead_keys.extend(dead_keys);
+        self
+    }
+
+    /// The US standard layout used by [`crate::to_keystrokes_new`] and [`crate::to_keystrokes_mut`].
+    pub fn us() -> &'static KeyboardLayout {
+        static US: OnceLock<KeyboardLayout> = OnceLock::new();
+        US.get_or_init(|| KeyboardLayout::new("US", us_mappings()))
+    }
+
+    /// Sets the virtual key `scan` produces on this layout for keys that differ from the US table in
+    /// [`crate::scan_code`], e.g. `(0x15, VirtualKey::KEY_Z)` for a German keyboard.
+    pub fn with_scan_codes(mut self, scan_codes: Vec<(u16, VirtualKey)>) -> KeyboardLayout {
+        self.scan_codes.extend(scan_codes);
+        self
+    }
+
+    /// The German standard layout, `KBDGR.DLL` on Windows, with the `^`, `´` and `` ` `` dead keys.
+    /// AltGr characters use [`ShiftState::CTRL`] and [`ShiftState::ALT`] together, the way
+    /// `VkKeyScanExW` reports them.
+    pub fn german() -> &'static KeyboardLayout {
+        static GERMAN: OnceLock<KeyboardLayout> = OnceLock::new();
+        GERMAN.get_or_init(|| KeyboardLayout::new("German", german_mappings())
+            .with_dead_keys(german_dead_keys())
+            .with_scan_codes(german_scan_codes()))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Every mapping in the layout, in the order they were defined.
+    pub fn mappings(&self) -> &[KeyMapping] {
+        &self.mappings
+    }
+
+    /// Finds the mapping used to type `character`.
+    pub fn lookup(&self, character: char) -> Option<&KeyMapping> {
+        self.by_char.get(&character).map(|&i| &self.mappings[i])
+    }
+
+    /// Finds the mapping produced by pressing `vk` while exactly `shift_state` is held.
+    pub fn lookup_key(&self, vk: VirtualKey, shift_state: ShiftState) -> Option<&KeyMapping> {
+        self.by_key.get(&(vk, shift_state)).map(|&i| &self.mappings[i])
+    }
+
+    pub fn dead_keys(&self) -> &[DeadKey] {
+        &self.dead_keys
+    }
+
+    /// Finds the dead key pressed with `vk` while exactly `shift_state` is held.
+    pub fn dead_key(&self, vk: VirtualKey, shift_state: ShiftState) -> Option<&DeadKey> {
+        self.dead_keys.iter().find(|dead_key| dead_key.vk == vk && dead_key.shift_state == shift_state)
+    }
+
+    /// The virtual key the physical key `scan` produces on this layout.
+    pub fn key_for_scan_code(&self, scan: u16) -> Option<VirtualKey> {
+        match self.scan_codes.iter().find(|&&(known, _)| known == scan) {
+            Some(&(_, vk)) => Some(vk),
+            None => virtual_key(scan),
+        }
+    }
+
+    /// The physical key that produces `vk` on this layout, like [`crate::scan_code::scan_code`] for
+    /// the US layout.
+    pub fn scan_code_for(&self, vk: VirtualKey) -> Option<u16> {
+        if let Some(&(scan, _)) = self.scan_codes.iter().find(|&&(_, known)| known == vk) {
+            return Some(scan)
+        }
+        scan_code(vk).filter(|&scan| self.key_for_scan_code(scan) == Some(vk))
+    }
+
+    /// Finds the keys that type `character`, preferring a single key over a dead key composition.
+    pub fn keys_for(&self, character: char) -> Option<KeyPlan<'_>> {
+        if let Some(mapping) = self.lookup(character) {
+            return Some(KeyPlan::Mapped(mapping))
+        }
+        self.dead_keys.iter().find_map(|dead_key| {
+            let &(base, _) = dead_key.compositions.iter().find(|&&(_, composed)| composed == character)?;
+            self.lookup(base).map(|mapping| KeyPlan::Composed(dead_key, mapping))
+        })
+    }
+}
+
+/// Layouts serialize as their name, mappings, dead keys and scan codes, the lookup indexes are rebuilt
+/// when deserializing. Dead keys and scan codes are left out when there are none.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct LayoutDefinition<'a> {
+    name: std::borrow::Cow<'a, str>,
+    mappings: std::borrow::Cow<'a, [KeyMapping]>,
+    #[serde(default, skip_serializing_if = "<[DeadKey]>::is_empty")]
+    dead_keys: std::borrow::Cow<'a, [DeadKey]>,
+    #[serde(default, skip_serializing_if = "<[(u16, VirtualKey)]>::is_empty")]
+    scan_codes: std::borrow::Cow<'a, [(u16, VirtualKey)]>,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for KeyboardLayout {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        LayoutDefinition {
+            name: (&*self.name).into(),
+            mappings: (&*self.mappings).into(),
+            dead_keys: (&*self.dead_keys).into(),
+            scan_codes: (&*self.scan_codes).into(),
+        }.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for KeyboardLayout {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let definition = LayoutDefinition::deserialize(deserializer)?;
+        Ok(KeyboardLayout::new(definition.name, definition.mappings.into_owned())
+            .with_dead_keys(definition.dead_keys.into_owned())
+            .with_scan_codes(definition.scan_codes.into_owned()))
+    }
+}
+
+/// Builds the US mappings from the same data [`crate::to_keystrokes_mut`] uses so both stay in sync.
+fn us_mappings() -> Vec<KeyMapping> {
+    let mut mappings = Vec::with_capacity(95);
+    for byte in ASCII_LOWERCASE_TO_UPPERCASE_OFFSET_AND_ASCII_MIN_VALUE..crate::MAX_VALID_ASCII_CHAR_SIZE {
+        let (vk, shift_state) = if (ascii::UPPERCASE_A..=ascii::UPPERCASE_Z).contains(&byte) {
+            (byte, ShiftState::SHIFT)
+        } else if (ascii::LOWERCASE_A..=ascii::LOWERCASE_Z).contains(&byte) {
+            (byte - ASCII_LOWERCASE_TO_UPPERCASE_OFFSET_AND_ASCII_MIN_VALUE, ShiftState::NONE)
+        } else if (ascii::ZERO..=ascii::NINE).contains(&byte) {
+            (byte, ShiftState::NONE)
+        } else if let Some(code) = CHAR_TO_KEY_MAP.get(&byte) {
+            ((code & 0xFF) as u8, ShiftState::from_bits((code >> 0x8) as u8))
+        } else {
+            continue
+        };
+        mappings.push(KeyMapping { character: byte as char, vk: VirtualKey(vk), shift_state });
+    }
+    mappings
+}
+
+fn german_mappings() -> Vec<KeyMapping> {
+    const ALT_GR: ShiftState = ShiftState::CTRL.union(ShiftState::ALT);
+    let mut mappings = Vec::with_capacity(110);
+    let mut add = |character: char, vk: VirtualKey, shift_state: ShiftState| {
+        mappings.push(KeyMapping { character, vk, shift_state });
+    };
+
+    for letter in 'a'..='z' {
+        let vk = VirtualKey(letter.to_ascii_uppercase() as u8);
+        add(letter, vk, ShiftState::NONE);
+        add(letter.to_ascii_uppercase(), vk, ShiftState::SHIFT);
+    }
+    for (digit, shifted) in ('0'..='9').zip(['=', '!', '"', '§', '$', '%', '&', '/', '(', ')']) {
+        let vk = VirtualKey(digit as u8);
+        add(digit, vk, ShiftState::NONE);
+        add(shifted, vk, ShiftState::SHIFT);
+    }
+
+    // Unshifted, shifted and AltGr characters, ^ is a dead key so only its shifted ° is typed
+    let keys = [
+        (VirtualKey::OEM_4, Some('ß'), Some('?'), Some('\\')),
+        (VirtualKey::OEM_5, None, Some('°'), None),
+        (VirtualKey::OEM_1, Some('ü'), Some('Ü'), None),
+        (VirtualKey::OEM_PLUS, Some('+'), Some('*'), Some('~')),
+        (VirtualKey::OEM_3, Some('ö'), Some('Ö'), None),
+        (VirtualKey::OEM_7, Some('ä'), Some('Ä'), None),
+        (VirtualKey::OEM_2, Some('#'), Some('\''), None),
+        (VirtualKey::OEM_102, Some('<'), Some('>'), Some('|')),
+        (VirtualKey::OEM_COMMA, Some(','), Some(';'), None),
+        (VirtualKey::OEM_PERIOD, Some('.'), Some(':'), None),
+        (VirtualKey::OEM_MINUS, Some('-'), Some('_'), None),
+    ];
+    for (vk, plain, shifted, alt_gr) in keys {
+        for (character, shift_state) in [(plain, ShiftState::NONE), (shifted, ShiftState::SHIFT), (alt_gr, ALT_GR)] {
+            if let Some(character) = character {
+                add(character, vk, shift_state);
+            }
+        }
+    }
+
+    for (character, vk) in [
+        ('²', VirtualKey::KEY_2), ('³', VirtualKey::KEY_3), ('{', VirtualKey::KEY_7), ('[', VirtualKey::KEY_8),
+        (']', VirtualKey::KEY_9), ('}', VirtualKey::KEY_0), ('@', VirtualKey::KEY_Q), ('€', VirtualKey::KEY_E),
+        ('µ', VirtualKey::KEY_M),
+    ] {
+        add(character, vk, ALT_GR);
+    }
+    add(' ', VirtualKey::SPACE, ShiftState::NONE);
+
+    // The numpad types the same symbols as on a US keyboard
+    for (character, vk) in [('*', VirtualKey::MULTIPLY), ('-', VirtualKey::SUBTRACT), ('/', VirtualKey::DIVIDE), ('+', VirtualKey::ADD)] {
+        add(character, vk, ShiftState::NONE);
+    }
+    mappings
+}
+
+fn german_dead_keys() -> Vec<DeadKey> {
+    let dead_key = |vk: VirtualKey, shift_state: ShiftState, accent: char, bases: &str, composed: &str| {
+        let mut compositions: Vec<(char, char)> = bases.chars().zip(composed.chars()).collect();
+        compositions.push((' ', accent));
+        DeadKey { vk, shift_state, compositions }
+    };
+
+    vec![
+        dead_key(VirtualKey::OEM_5, ShiftState::NONE, '^', "aeiouAEIOU", "âêîôûÂÊÎÔÛ"),
+        dead_key(VirtualKey::OEM_6, ShiftState::NONE, '´', "aeiouyAEIOUY", "áéíóúýÁÉÍÓÚÝ"),
+        dead_key(VirtualKey::OEM_6, ShiftState::SHIFT, '`', "aeiouAEIOU", "àèìòùÀÈÌÒÙ"),
+    ]
+}
+
+/// Keys of a German keyboard that produce a different virtual key than on a US one.
+fn german_scan_codes() -> Vec<(u16, VirtualKey)> {
+    vec![
+        (0x0C, VirtualKey::OEM_4),
+        (0x0D, VirtualKey::OEM_6),
+        (0x15, VirtualKey::KEY_Z),
+        (0x1A, VirtualKey::OEM_1),
+        (0x1B, VirtualKey::OEM_PLUS),
+        (0x27, VirtualKey::OEM_3),
+        (0x29, VirtualKey::OEM_5),
+        (0x2B, VirtualKey::OEM_2),
+        (0x2C, VirtualKey::KEY_Y),
+        (0x35, VirtualKey::OEM_MINUS),
+    ]
+}
+
+/// Translates a character into the virtual key and modifiers that type it on `layout`, like
+/// `VkKeyScanExW`. Returns `None` when the layout has no key for the character.
+pub fn vk_key_scan(character: char, layout: &KeyboardLayout) -> Option<(VirtualKey, ShiftState)> {
+    layout.lookup(character).map(|mapping| (mapping.vk, mapping.shift_state))
+}
+
+/// Same as [`vk_key_scan`] but packed the way `VkKeyScanExW` returns it: the low byte is the virtual
+/// key, the high byte is the shift state and `-1` means no key was found.
+pub fn vk_key_scan_ex(character: char, layout: &KeyboardLayout) -> i16 {
+    match vk_key_scan(character, layout) {
+        Some((vk, shift_state)) => i16::from_le_bytes([vk.0, shift_state.bits()]),
+        None => -1,
+    }
+}
+
+/// Translates a virtual key pressed with the modifiers in `modifier_state` into the character it
+/// types on `layout`, like `ToUnicodeEx`. This is the inverse of [`vk_key_scan`] and reads the same
+/// mappings, so `to_unicode(vk, state, layout)` gives back the character `vk_key_scan` was given.
+///
+/// Returns `None` when the combination does not type a character on the layout.
+pub fn to_unicode(vk: VirtualKey, modifier_state: ShiftState, layout: &KeyboardLayout) -> Option<char> {
+    layout.lookup_key(vk, modifier_state).map(|mapping| mapping.character)
+}
+
+/// Translates `keys` like [`crate::to_keystrokes_new`], using `layout` for the lookups. Modifiers
+/// other than shift, such as the CTRL and ALT pair behind AltGr, are pressed and released the same way
+/// shift is, and characters no single key types are composed with the layout's dead keys.
+pub fn to_keystrokes_with_layout(keys: &str, layout: &KeyboardLayout) -> Result<Vec<u8>, KeyError> {
+    let mut keystrokes = Vec::with_capacity(keys.len());
+    let mut encoder = ModifierEncoder::new(&mut keystrokes);
+    for character in keys.chars() {
+        for (vk, shift_state) in layout.keys_for(character).ok_or_else(|| KeyError::not_found(character))?.keys() {
+            encoder.push(vk, shift_state);
+        }
+    }
+    encoder.finish();
+    Ok(keystrokes)
+}
+
+
+
+/* ### --- UNIT TEST --- ### */
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lowercase_has_no_shift() {
+        assert_eq!(vk_key_scan('a', KeyboardLayout::us()), Some((VirtualKey::KEY_A, ShiftState::NONE)));
+    }
+
+    #[test]
+    fn test_uppercase_requires_shift() {
+        assert_eq!(vk_key_scan('Z', KeyboardLayout::us()), Some((VirtualKey::KEY_Z, ShiftState::SHIFT)));
+    }
+
+    #[test]
+    fn test_symbol_from_map() {
+        assert_eq!(vk_key_scan('?', KeyboardLayout::us()), Some((VirtualKey::OEM_2, ShiftState::SHIFT)));
+        assert_eq!(vk_key_scan(';', KeyboardLayout::us()), Some((VirtualKey::OEM_1, ShiftState::NONE)));
+    }
+
+    #[test]
+    fn test_not_found() {
+        assert_eq!(vk_key_scan('æ', KeyboardLayout::us()), None);
+        assert_eq!(vk_key_scan('\n', KeyboardLayout::us()), None);
+        assert_eq!(vk_key_scan_ex('æ', KeyboardLayout::us()), -1);
+    }
+
+    #[test]
+    fn test_packed_matches_win32() {
+        // Values as returned by VkKeyScanExW on a US layout
+        assert_eq!(vk_key_scan_ex('a', KeyboardLayout::us()), 0x0041);
+        assert_eq!(vk_key_scan_ex('A', KeyboardLayout::us()), 0x0141);
+        assert_eq!(vk_key_scan_ex('!', KeyboardLayout::us()), 0x0131);
+    }
+
+    #[test]
+    fn test_full_shift_state_is_kept() {
+        let layout = KeyboardLayout::new("test", vec![KeyMapping {
+            character: '€',
+            vk: VirtualKey::KEY_E,
+            shift_state: ShiftState::CTRL | ShiftState::ALT,
+        }]);
+
+        assert_eq!(vk_key_scan_ex('€', &layout), 0x0645);
+        let (_, shift_state) = vk_key_scan('€', &layout).unwrap();
+        assert!(shift_state.contains(ShiftState::CTRL));
+        assert!(shift_state.contains(ShiftState::ALT));
+        assert!(!shift_state.contains(ShiftState::SHIFT));
+    }
+
+    #[test]
+    fn test_first_mapping_wins() {
+        let layout = KeyboardLayout::new("test", vec![
+            KeyMapping { character: '-', vk: VirtualKey::SUBTRACT, shift_state: ShiftState::NONE },
+            KeyMapping { character: '-', vk: VirtualKey::OEM_MINUS, shift_state: ShiftState::NONE },
+        ]);
+
+        assert_eq!(vk_key_scan('-', &layout), Some((VirtualKey::SUBTRACT, ShiftState::NONE)));
+    }
+
+    #[test]
+    fn test_to_unicode_letters() {
+        assert_eq!(to_unicode(VirtualKey::KEY_A, ShiftState::NONE, KeyboardLayout::us()), Some('a'));
+        assert_eq!(to_unicode(VirtualKey::KEY_A, ShiftState::SHIFT, KeyboardLayout::us()), Some('A'));
+    }
+
+    #[test]
+    fn test_to_unicode_digits_and_symbols() {
+        assert_eq!(to_unicode(VirtualKey::KEY_1, ShiftState::NONE, KeyboardLayout::us()), Some('1'));
+        assert_eq!(to_unicode(VirtualKey::KEY_1, ShiftState::SHIFT, KeyboardLayout::us()), Some('!'));
+        assert_eq!(to_unicode(VirtualKey::OEM_7, ShiftState::SHIFT, KeyboardLayout::us()), Some('"'));
+    }
+
+    #[test]
+    fn test_to_unicode_unmapped() {
+        assert_eq!(to_unicode(VirtualKey::F1, ShiftState::NONE, KeyboardLayout::us()), None);
+        assert_eq!(to_unicode(VirtualKey::KEY_A, ShiftState::CTRL, KeyboardLayout::us()), None);
+    }
+
+    #[test]
+    fn test_both_directions_are_consistent() {
+        let layout = KeyboardLayout::us();
+        for mapping in layout.mappings() {
+            let (vk, shift_state) = vk_key_scan(mapping.character, layout).unwrap();
+            assert_eq!(to_unicode(vk, shift_state, layout), Some(mapping.character),
+                "{:?} did not round trip", mapping.character);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_shift_state() {
+        assert_eq!(serde_json::to_string(&(ShiftState::CTRL | ShiftState::ALT)).unwrap(), r#"["CTRL","ALT"]"#);
+        assert_eq!(serde_json::to_string(&ShiftState::from_bits(0x11)).unwrap(), "17");
+        assert_eq!(serde_json::from_str::<ShiftState>(r#"["shift"]"#).unwrap(), ShiftState::SHIFT);
+        assert_eq!(serde_json::from_str::<ShiftState>("6").unwrap(), ShiftState::CTRL | ShiftState::ALT);
+        assert!(serde_json::from_str::<ShiftState>(r#"["META"]"#).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_layout() {
+        let json = r#"{"name":"test","mappings":[{"character":"€","vk":"VK_E","shift_state":["CTRL","ALT"]}]}"#;
+        let layout: KeyboardLayout = serde_json::from_str(json).unwrap();
+
+        assert_eq!(vk_key_scan('€', &layout), Some((VirtualKey::KEY_E, ShiftState::CTRL | ShiftState::ALT)));
+        assert_eq!(serde_json::to_string(&layout).unwrap(), json);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_us_layout_round_trip() {
+        let json = serde_json::to_string(KeyboardLayout::us()).unwrap();
+        let layout: KeyboardLayout = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(layout.mappings(), KeyboardLayout::us().mappings());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_dead_keys_round_trip() {
+        let json = serde_json::to_string(KeyboardLayout::german()).unwrap();
+        let layout: KeyboardLayout = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(layout.dead_keys(), KeyboardLayout::german().dead_keys());
+        assert_eq!(layout.keys_for('ô'), KeyboardLayout::german().keys_for('ô'));
+        assert_eq!(layout.key_for_scan_code(0x15), Some(VirtualKey::KEY_Z));
+    }
+
+    #[test]
+    fn test_german_layout() {
+        let layout = KeyboardLayout::german();
+
+        assert_eq!(vk_key_scan('z', layout), Some((VirtualKey::KEY_Z, ShiftState::NONE)));
+        assert_eq!(vk_key_scan('ß', layout), Some((VirtualKey::OEM_4, ShiftState::NONE)));
+        assert_eq!(vk_key_scan_ex('@', layout), 0x0651);
+        assert_eq!(vk_key_scan('^', layout), None);
+        assert_eq!(vk_key_scan('é', layout), None);
+        assert_eq!(to_unicode(VirtualKey::KEY_7, ShiftState::SHIFT, layout), Some('/'));
+    }
+
+    #[test]
+    fn test_to_keystrokes_with_layout() {
+        assert_eq!(to_keystrokes_with_layout("Hello, World!", KeyboardLayout::us()), crate::to_keystrokes_new("Hello, World!"));
+        // AltGr is pressed as CTRL then ALT and released in reverse
+        assert_eq!(to_keystrokes_with_layout("a@Ü", KeyboardLayout::german()).unwrap(), vec![0x41, 0x11, 0x12, 0x51, 0x12, 0x11, 0x10, 0xBA, 0x10]);
+        assert!(to_keystrokes_with_layout("æ", KeyboardLayout::german()).is_err());
+    }
+
+    #[test]
+    fn test_dead_keys() {
+        let layout = KeyboardLayout::german();
+        let Some(KeyPlan::Composed(dead_key, mapping)) = layout.keys_for('È') else { panic!("È should be composed") };
+
+        assert_eq!((dead_key.vk, dead_key.shift_state), (VirtualKey::OEM_6, ShiftState::SHIFT));
+        assert_eq!(mapping.character, 'E');
+        assert_eq!(to_keystrokes_with_layout("é^", layout).unwrap(), vec![0xDD, 0x45, 0xDC, 0x20]);
+        assert!(KeyboardLayout::us().keys_for('é').is_none());
+    }
+
+    #[test]
+    fn test_scan_codes() {
+        let german = KeyboardLayout::german();
+
+        assert_eq!(german.key_for_scan_code(0x15), Some(VirtualKey::KEY_Z));
+        assert_eq!(german.scan_code_for(VirtualKey::KEY_Y), Some(0x2C));
+        assert_eq!(german.scan_code_for(VirtualKey::OEM_MINUS), Some(0x35));
+        assert_eq!(german.scan_code_for(VirtualKey::KEY_A), Some(0x1E));
+        assert_eq!(german.scan_code_for(VirtualKey::OEM_7), Some(0x28));
+        assert_eq!(KeyboardLayout::us().scan_code_for(VirtualKey::KEY_Y), Some(0x15));
+    }
+
+    #[test]
+    fn test_us_layout_agrees_with_to_keystrokes() {
+        let mappings = KeyboardLayout::us().mappings();
+        assert_eq!(mappings.len(), 95, "every printable ascii character should be mapped");
+
+        for mapping in mappings {
+            let strokes = crate::to_keystrokes_new(&mapping.character.to_string()).unwrap();
+            let expected = if mapping.shift_state == ShiftState::SHIFT {
+                vec![crate::vk::VK_SHIFT, mapping.vk.0, crate::vk::VK_SHIFT]
+            } else {
+                vec![mapping.vk.0]
+            };
+            assert_eq!(strokes, expected, "mismatch for {:?}", mapping.character);
+        }
+    }
+}
@@ -1,5 +1,60 @@
 use phf::phf_map;
 
+pub mod analysis;
+pub mod batch;
+pub mod compare;
+pub mod coverage;
+pub mod geometry;
+pub mod keystroke;
+pub mod layout;
+pub mod notation;
+pub mod policy;
+pub mod recording;
+pub mod registry;
+pub mod rhythm;
+mod rng;
+pub mod safety;
+pub mod scan_code;
+#[cfg(feature = "zeroize")]
+pub mod secret;
+pub mod schedule;
+pub mod sender;
+pub mod sequence;
+pub mod simulator;
+pub mod source_map;
+pub mod timeline;
+pub mod typematic;
+pub mod typo;
+pub mod validate;
+pub mod virtual_key;
+
+pub use analysis::TypingStats;
+pub use batch::Batcher;
+pub use compare::{matching_layouts, predict_mojibake, predict_text, LayoutDiff, ReplayMode};
+pub use coverage::{rank_layouts, CoverageReport, Strategy};
+pub use geometry::{KeyPosition, PhysicalLayout};
+pub use keystroke::{decode_keystrokes, Keystroke};
+pub use layout::{to_keystrokes_with_layout, to_unicode, vk_key_scan, vk_key_scan_ex, DeadKey, KeyboardLayout, KeyMapping, KeyPlan, ShiftState};
+pub use notation::{format_keystrokes, parse_notation, to_notation, NotationStyle};
+pub use policy::{Chord, KeyPolicy};
+pub use recording::{decode_recording, encode_recording, RecordingReader, RecordingWriter};
+pub use registry::{to_keystrokes_for, LayoutRegistry, RegisteredLayout};
+pub use rhythm::RhythmModel;
+pub use safety::{cleanup, cleanup_keystrokes, release_all_modifiers, ReleaseGuard};
+pub use scan_code::{map_virtual_key, MapType};
+pub use schedule::TypingScheduler;
+#[cfg(feature = "zeroize")]
+pub use secret::{to_keystrokes_secret, SecretKeystrokes};
+pub use sender::{KeystrokeSender, RecordingSender};
+pub use source_map::{MappedKeystroke, SourceMap};
+pub use sequence::KeySequence;
+pub use simulator::{replay, Replay, VirtualKeyboard};
+pub use timeline::Timeline;
+pub use typematic::{hold_keystrokes, Typematic};
+pub use typo::TypoSimulator;
+pub use validate::{canonicalize, canonicalize_keystrokes, validate, validate_keystrokes};
+pub use virtual_key::VirtualKey;
+
 mod ascii {
     // Uppercase letters
     pub const UPPERCASE_A: u8 = 0x41; // 'A'
@@ -20,15 +75,19 @@ mod vk {
 /// Errors that can occur when mapping a character to a key.
 #[derive(PartialEq)]
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ErrorCodes {
     /// Was unable to find a matching key for the given character.
     NotFound,
     /// The given character was outside the valid ASCII character range.
-    OutOfRange
+    OutOfRange,
+    /// The layout identifier did not match any layout in the registry.
+    UnknownLayout
 }
 
 #[derive(PartialEq)]
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct KeyError {
     /// The byte being processed when the error occurred. Do not depend on this field as it can vary
     /// wildly because not all characters occupy a single byte in memory. Rather this should be used
@@ -38,6 +97,17 @@ pub struct KeyError {
     pub error_code: ErrorCodes
 }
 
+impl KeyError {
+    /// The error for a character the layout cannot type, reporting its first UTF-8 byte.
+    pub(crate) fn not_found(character: char) -> KeyError {
+        let mut bytes = [0; 4];
+        KeyError {
+            byte: character.encode_utf8(&mut bytes).as_bytes()[0],
+            error_code: ErrorCodes::NotFound
+        }
+    }
+}
+
 /// The offset in the ASCII character table from [a-z] and [A-Z].
 /// Can be calculated by subtracting the code value of 'a' from 'A'.
 const ASCII_LOWERCASE_TO_UPPERCASE_OFFSET_AND_ASCII_MIN_VALUE: u8 = 32;
@@ -90,6 +160,7 @@ pub fn to_keystrokes_new(keys: &str) -> Result<Vec<u8>, KeyError> {
     Ok(keystrokes)
 }
 
+#[allow(clippy::needless_range_loop, clippy::manual_range_contains)]
 pub fn to_keystrokes_mut(keys: &str, keystrokes: &mut Vec<u8>) -> Result<(), KeyError> {
     let characters = keys.as_bytes();
     let length = keys.len();
@@ -234,6 +305,7 @@ mod tests {
     pub const VK_OEM_7: u8 = 0xDE; // OEM 7 - For single quote (') and double quote (")
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_non_valid_character_results_in_error() {
         let strokes = to_keystrokes_new("æ");
 
@@ -0,0 +1,242 @@
+use std::fmt;
+
+use crate::keystroke::{decode_keystrokes, Keystroke};
+use crate::virtual_key::VirtualKey;
+
+/// How [`to_notation`] renders key events.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum NotationStyle {
+    /// Symbols for common keys and arrows for presses and releases, e.g. `⇧↓ H ⇧↑ E L L O`.
+    /// Auto-repeats are marked with `↻`, e.g. `⌫↓ ⌫↻ ⌫↻ ⌫↑`.
+    #[default]
+    Unicode,
+    /// Plain ASCII with `+` for presses and `-` for releases, e.g. `+SHIFT H -SHIFT E L L O`.
+    /// Auto-repeats are marked with `*`, e.g. `+BACK *BACK *BACK -BACK`.
+    Ascii,
+}
+
+/// Errors that can occur when parsing key notation.
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ParseErrorCodes {
+    /// A press or release marker was not attached to a key.
+    MissingKey,
+    /// The key name is neither a symbol, a virtual key name nor a hex code.
+    UnknownKey,
+}
+
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParseError {
+    /// Byte offset of the offending token in the notation.
+    pub position: usize,
+    /// Type of error that occurred.
+    pub error_code: ParseErrorCodes
+}
+
+/// Symbols used by [`NotationStyle::Unicode`]. Keys not listed here are written by name.
+static SYMBOLS: &[(VirtualKey, &str)] = &[
+    (VirtualKey::SHIFT, "⇧"),
+    (VirtualKey::LSHIFT, "L⇧"),
+    (VirtualKey::RSHIFT, "R⇧"),
+    (VirtualKey::CONTROL, "⌃"),
+    (VirtualKey::LCONTROL, "L⌃"),
+    (VirtualKey::RCONTROL, "R⌃"),
+    (VirtualKey::MENU, "⎇"),
+    (VirtualKey::LMENU, "L⎇"),
+    (VirtualKey::RMENU, "R⎇"),
+    (VirtualKey::LWIN, "L⊞"),
+    (VirtualKey::RWIN, "R⊞"),
+    (VirtualKey::SPACE, "␣"),
+    (VirtualKey::RETURN, "↵"),
+    (VirtualKey::BACK, "⌫"),
+    (VirtualKey::TAB, "⇥"),
+    (VirtualKey::ESCAPE, "⎋"),
+];
+
+const DOWN_ARROW: char = '↓';
+const UP_ARROW: char = '↑';
+const REPEAT_ARROW: char = '↻';
+const DOWN_PREFIX: char = '+';
+const UP_PREFIX: char = '-';
+const REPEAT_PREFIX: char = '*';
+
+/// Writes the name of a single key in the given style.
+fn write_key(f: &mut impl fmt::Write, vk: VirtualKey, style: NotationStyle) -> fmt::Result {
+    if style == NotationStyle::Unicode
+        && let Some(&(_, symbol)) = SYMBOLS.iter().find(|&&(known, _)| known == vk) {
+        return f.write_str(symbol)
+    }
+    match vk.name() {
+        Some(name) => f.write_str(&name[3..]),
+        None => write!(f, "{:#04X}", vk.0),
+    }
+}
+
+fn write_keystroke(f: &mut impl fmt::Write, keystroke: Keystroke, style: NotationStyle) -> fmt::Result {
+    match (keystroke, style) {
+        (Keystroke::Down(vk), NotationStyle::Unicode) => { write_key(f, vk, style)?; f.write_char(DOWN_ARROW) },
+        (Keystroke::Up(vk), NotationStyle::Unicode) => { write_key(f, vk, style)?; f.write_char(UP_ARROW) },
+        (Keystroke::Repeat(vk), NotationStyle::Unicode) => { write_key(f, vk, style)?; f.write_char(REPEAT_ARROW) },
+        (Keystroke::Down(vk), NotationStyle::Ascii) => { f.write_char(DOWN_PREFIX)?; write_key(f, vk, style) },
+        (Keystroke::Up(vk), NotationStyle::Ascii) => { f.write_char(UP_PREFIX)?; write_key(f, vk, style) },
+        (Keystroke::Repeat(vk), NotationStyle::Ascii) => { f.write_char(REPEAT_PREFIX)?; write_key(f, vk, style) },
+        (Keystroke::Tap(vk), _) => write_key(f, vk, style),
+    }
+}
+
+impl fmt::Display for Keystroke {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_keystroke(f, *self, NotationStyle::Unicode)
+    }
+}
+
+/// Renders key events as space separated notation that [`parse_notation`] reads back.
+pub fn to_notation(keystrokes: &[Keystroke], style: NotationStyle) -> String {
+    let mut notation = String::new();
+    for (i, &keystroke) in keystrokes.iter().enumerate() {
+        if i > 0 {
+            notation.push(' ');
+        }
+        // Writing into a String never fails
+        let _ = write_keystroke(&mut notation, keystroke, style);
+    }
+    notation
+}
+
+/// Renders the flat output of [`crate::to_keystrokes_new`] as notation.
+pub fn format_keystrokes(keystrokes: &[u8], style: NotationStyle) -> String {
+    to_notation(&decode_keystrokes(keystrokes), style)
+}
+
+/// Parses notation written in either [`NotationStyle`], or a mix of both. Keys can be given as a
+/// symbol, a virtual key name with or without the `VK_` prefix, or a hex code such as `0x41`.
+pub fn parse_notation(notation: &str) -> Result<Vec<Keystroke>, ParseError> {
+    let mut keystrokes = Vec::new();
+    let mut rest = notation;
+
+    while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+        let token_len = rest[start..].find(char::is_whitespace).unwrap_or(rest.len() - start);
+        let token = &rest[start..start + token_len];
+        let position = notation.len() - rest.len() + start;
+        keystrokes.push(parse_token(token, position)?);
+        rest = &rest[start + token_len..];
+    }
+
+    Ok(keystrokes)
+}
+
+fn parse_token(token: &str, position: usize) -> Result<Keystroke, ParseError> {
+    let (name, make): (&str, fn(VirtualKey) -> Keystroke) =
+        if let Some(name) = token.strip_suffix(DOWN_ARROW) {
+            (name, Keystroke::Down)
+        } else if let Some(name) = token.strip_suffix(UP_ARROW) {
+            (name, Keystroke::Up)
+        } else if let Some(name) = token.strip_suffix(REPEAT_ARROW) {
+            (name, Keystroke::Repeat)
+        } else if let Some(name) = token.strip_prefix(DOWN_PREFIX) {
+            (name, Keystroke::Down)
+        } else if let Some(name) = token.strip_prefix(UP_PREFIX) {
+            (name, Keystroke::Up)
+        } else if let Some(name) = token.strip_prefix(REPEAT_PREFIX) {
+            (name, Keystroke::Repeat)
+        } else {
+            (token, Keystroke::Tap)
+        };
+
+    if name.is_empty() {
+        return Err(ParseError { position, error_code: ParseErrorCodes::MissingKey })
+    }
+
+    parse_key(name)
+        .map(make)
+        .ok_or(ParseError { position, error_code: ParseErrorCodes::UnknownKey })
+}
+
+fn parse_key(name: &str) -> Option<VirtualKey> {
+    if let Some(&(vk, _)) = SYMBOLS.iter().find(|&&(_, symbol)| symbol == name) {
+        return Some(vk)
+    }
+    VirtualKey::parse(name)
+}
+
+
+
+/* ### --- UNIT TEST --- ### */
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unicode_format() {
+        let strokes = crate::to_keystrokes_new("Hello").unwrap();
+
+        assert_eq!(format_keystrokes(&strokes, NotationStyle::Unicode), "⇧↓ H ⇧↑ E L L O");
+    }
+
+    #[test]
+    fn test_ascii_format() {
+        let strokes = crate::to_keystrokes_new("Hi, you!").unwrap();
+
+        assert_eq!(format_keystrokes(&strokes, NotationStyle::Ascii),
+            "+SHIFT H -SHIFT I OEM_COMMA SPACE Y O U +SHIFT 1 -SHIFT");
+    }
+
+    #[test]
+    fn test_unnamed_key_is_hex() {
+        assert_eq!(to_notation(&[Keystroke::Tap(VirtualKey(0x07))], NotationStyle::Ascii), "0x07");
+        assert_eq!(parse_notation("0x07").unwrap(), vec![Keystroke::Tap(VirtualKey(0x07))]);
+    }
+
+    #[test]
+    fn test_display_uses_unicode() {
+        assert_eq!(Keystroke::Down(VirtualKey::RCONTROL).to_string(), "R⌃↓");
+        assert_eq!(Keystroke::Tap(VirtualKey::SPACE).to_string(), "␣");
+    }
+
+    #[test]
+    fn test_round_trip_both_styles() {
+        let strokes = crate::decode_keystrokes(&crate::to_keystrokes_new("ZbcAzCaZc {x} ~!").unwrap());
+
+        for style in [NotationStyle::Unicode, NotationStyle::Ascii] {
+            assert_eq!(parse_notation(&to_notation(&strokes, style)).unwrap(), strokes, "{style:?} did not round trip");
+        }
+    }
+
+    #[test]
+    fn test_repeat_notation() {
+        let strokes = crate::hold_keystrokes(VirtualKey::BACK, 2);
+
+        assert_eq!(to_notation(&strokes, NotationStyle::Unicode), "⌫↓ ⌫↻ ⌫↻ ⌫↑");
+        assert_eq!(to_notation(&strokes, NotationStyle::Ascii), "+BACK *BACK *BACK -BACK");
+        assert_eq!(parse_notation("+BACK ⌫↻ *BACK -BACK").unwrap(), strokes);
+    }
+
+    #[test]
+    fn test_parse_fixture() {
+        let expected = parse_notation("+shift VK_A -SHIFT   b\tc").unwrap();
+
+        assert_eq!(crate::decode_keystrokes(&crate::to_keystrokes_new("Abc").unwrap()), expected);
+    }
+
+    #[test]
+    fn test_parse_unknown_key() {
+        assert_eq!(parse_notation("A ⇧↓ NOPE"), Err(ParseError { position: 9, error_code: ParseErrorCodes::UnknownKey }));
+    }
+
+    #[test]
+    fn test_parse_missing_key() {
+        assert_eq!(parse_notation("A +"), Err(ParseError { position: 2, error_code: ParseErrorCodes::MissingKey }));
+        assert_eq!(parse_notation("↑"), Err(ParseError { position: 0, error_code: ParseErrorCodes::MissingKey }));
+    }
+
+    #[test]
+    fn test_parse_empty() {
+        assert_eq!(parse_notation("  ").unwrap(), vec![]);
+    }
+}
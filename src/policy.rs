@@ -0,0 +1,298 @@
+use std::fmt;
+
+use crate::keystroke::{decode_keystrokes, is_modifier, Keystroke};
+use crate::layout::{to_unicode, KeyboardLayout, ShiftState};
+use crate::safety::HeldKeys;
+use crate::virtual_key::VirtualKey;
+
+/// Errors that can occur when checking keystrokes against a [`KeyPolicy`].
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PolicyErrorCodes {
+    /// The key is on the deny list, or an allow list is set and the key is not on it.
+    DeniedKey,
+    /// The keys held down together match a denied chord.
+    DeniedChord,
+    /// More keys are held down together than [`KeyPolicy::max_chord_size`] allows.
+    ChordTooLarge,
+    /// [`KeyPolicy::printable_only`] is set and the key does not type a printable character.
+    NotPrintable,
+}
+
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PolicyError {
+    /// Index of the offending event. For the flat `Vec<u8>` format this is also the byte index.
+    pub position: usize,
+    /// The key being pressed when the violation occurred.
+    pub vk: VirtualKey,
+    /// Type of error that occurred.
+    pub error_code: PolicyErrorCodes
+}
+
+/// A set of keys pressed together, such as `Ctrl+Alt+Delete`. Left and right modifiers match their
+/// generic key and both Windows keys match [`VirtualKey::LWIN`].
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Chord {
+    keys: Vec<VirtualKey>,
+}
+
+impl Chord {
+    pub fn new(keys: impl IntoIterator<Item = VirtualKey>) -> Chord {
+        let mut keys: Vec<VirtualKey> = keys.into_iter().map(generic).collect();
+        keys.sort();
+        keys.dedup();
+        Chord { keys }
+    }
+
+    /// Parses keys separated by `+`, each accepted by [`VirtualKey::parse`], e.g. `LWIN+R` or
+    /// `VK_CONTROL+VK_MENU+VK_DELETE`.
+    pub fn parse(text: &str) -> Option<Chord> {
+        text.split('+')
+            .map(|key| VirtualKey::parse(key.trim()))
+            .collect::<Option<Vec<_>>>()
+            .map(Chord::new)
+    }
+
+    /// The keys in the chord, sorted by code.
+    pub fn keys(&self) -> &[VirtualKey] {
+        &self.keys
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Whether every key in `other` is also in this chord.
+    pub fn contains(&self, other: &Chord) -> bool {
+        other.keys.iter().all(|key| self.keys.contains(key))
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, key) in self.keys.iter().enumerate() {
+            if i > 0 {
+                f.write_str("+")?;
+            }
+            write!(f, "{key}")?;
+        }
+        Ok(())
+    }
+}
+
+fn generic(vk: VirtualKey) -> VirtualKey {
+    match vk {
+        VirtualKey::LSHIFT | VirtualKey::RSHIFT => VirtualKey::SHIFT,
+        VirtualKey::LCONTROL | VirtualKey::RCONTROL => VirtualKey::CONTROL,
+        VirtualKey::LMENU | VirtualKey::RMENU => VirtualKey::MENU,
+        VirtualKey::RWIN => VirtualKey::LWIN,
+        _ => vk,
+    }
+}
+
+/// Rules that generated input has to follow, for example when the text comes from an untrusted
+/// source and must not be able to send Win+R or Alt+F4.
+///
+/// Every key press is checked along with the keys already held down when it happens. Releases are
+/// never rejected, so input cut short by a violation can always be cleaned up.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct KeyPolicy {
+    /// When set, only these keys may be pressed. Left and right modifiers match their generic key and
+    /// both Windows keys match each other.
+    pub allowed_keys: Option<Vec<VirtualKey>>,
+    /// Keys that may never be pressed, matched like [`KeyPolicy::allowed_keys`].
+    pub denied_keys: Vec<VirtualKey>,
+    /// Chords that may never be pressed, also matched when more keys are held on top of them.
+    pub denied_chords: Vec<Chord>,
+    /// Chords that are always accepted, taking priority over [`KeyPolicy::denied_chords`] and
+    /// [`KeyPolicy::max_chord_size`].
+    pub allowed_chords: Vec<Chord>,
+    /// The most keys that may be held down together, the pressed key included.
+    pub max_chord_size: usize,
+    /// Only accept keys that type a printable character with the modifiers held, or that are a dead
+    /// key waiting for one. Modifiers are only accepted when the layout types characters with them,
+    /// so Shift always is and Ctrl and Alt only on layouts with AltGr.
+    pub printable_only: bool,
+}
+
+impl Default for KeyPolicy {
+    /// A policy that accepts everything.
+    fn default() -> Self {
+        KeyPolicy {
+            allowed_keys: None,
+            denied_keys: Vec::new(),
+            denied_chords: Vec::new(),
+            allowed_chords: Vec::new(),
+            max_chord_size: usize::MAX,
+            printable_only: false,
+        }
+    }
+}
+
+impl KeyPolicy {
+    /// A policy for text from untrusted sources: printable characters only, typed with at most Shift
+    /// and AltGr held.
+    pub fn text_only() -> KeyPolicy {
+        KeyPolicy { max_chord_size: 4, printable_only: true, ..KeyPolicy::default() }
+    }
+
+    /// Checks every event, returning the first violation.
+    pub fn check(&self, keystrokes: &[Keystroke], layout: &KeyboardLayout) -> Result<(), PolicyError> {
+        let mut held = HeldKeys::new();
+        for (position, &keystroke) in keystrokes.iter().enumerate() {
+            if let Keystroke::Down(vk) | Keystroke::Tap(vk) | Keystroke::Repeat(vk) = keystroke
+                && let Err(error_code) = self.check_press(vk, held.keys(), layout) {
+                return Err(PolicyError { position, vk, error_code })
+            }
+            held.track(keystroke);
+        }
+        Ok(())
+    }
+
+    /// Checks the flat output of [`crate::to_keystrokes_new`].
+    pub fn check_keystrokes(&self, keystrokes: &[u8], layout: &KeyboardLayout) -> Result<(), PolicyError> {
+        self.check(&decode_keystrokes(keystrokes), layout)
+    }
+
+    fn check_press(&self, vk: VirtualKey, held: &[VirtualKey], layout: &KeyboardLayout) -> Result<(), PolicyErrorCodes> {
+        // Left and right keys match their generic key, like in chords
+        let is_listed = |keys: &[VirtualKey]| keys.iter().any(|&listed| generic(listed) == generic(vk));
+        if self.allowed_keys.as_deref().is_some_and(|allowed| !is_listed(allowed)) || is_listed(&self.denied_keys) {
+            return Err(PolicyErrorCodes::DeniedKey)
+        }
+
+        if self.printable_only && !is_printable(vk, held, layout) {
+            return Err(PolicyErrorCodes::NotPrintable)
+        }
+
+        let chord = Chord::new(held.iter().copied().chain([vk]));
+        if self.allowed_chords.contains(&chord) {
+            return Ok(())
+        }
+        if self.denied_chords.iter().any(|denied| denied.keys.contains(&generic(vk)) && chord.contains(denied)) {
+            return Err(PolicyErrorCodes::DeniedChord)
+        }
+        if chord.len() > self.max_chord_size {
+            return Err(PolicyErrorCodes::ChordTooLarge)
+        }
+        Ok(())
+    }
+}
+
+fn is_printable(vk: VirtualKey, held: &[VirtualKey], layout: &KeyboardLayout) -> bool {
+    if is_modifier(vk) {
+        let modifier = ShiftState::for_modifier(vk);
+        return modifier == ShiftState::SHIFT || (!modifier.is_empty() && is_used(modifier, layout))
+    }
+    let shift_state = held.iter().fold(ShiftState::NONE, |shift_state, &held| shift_state | ShiftState::for_modifier(held));
+    to_unicode(vk, shift_state, layout).is_some_and(|character| !character.is_control())
+        || layout.dead_key(vk, shift_state).is_some()
+}
+
+/// Whether any character on `layout` is typed with `modifier` held, like Ctrl and Alt for AltGr.
+fn is_used(modifier: ShiftState, layout: &KeyboardLayout) -> bool {
+    layout.mappings().iter().any(|mapping| mapping.shift_state.contains(modifier))
+        || layout.dead_keys().iter().any(|dead_key| dead_key.shift_state.contains(modifier))
+}
+
+
+
+/* ### --- UNIT TEST --- ### */
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_notation;
+
+    fn us() -> &'static KeyboardLayout {
+        KeyboardLayout::us()
+    }
+
+    fn error(position: usize, vk: VirtualKey, error_code: PolicyErrorCodes) -> Result<(), PolicyError> {
+        Err(PolicyError { position, vk, error_code })
+    }
+
+    #[test]
+    fn test_default_accepts_everything() {
+        let keystrokes = parse_notation("L⊞↓ R L⊞↑ ⎇↓ F4 ⎇↑").unwrap();
+
+        assert_eq!(KeyPolicy::default().check(&keystrokes, us()), Ok(()));
+    }
+
+    #[test]
+    fn test_denied_chord_matches_supersets_and_sides() {
+        let policy = KeyPolicy { denied_chords: vec![Chord::parse("LWIN+R").unwrap()], ..KeyPolicy::default() };
+
+        assert_eq!(policy.check(&parse_notation("R⊞↓ ⇧↓ R ⇧↑ R⊞↑").unwrap(), us()), error(2, VirtualKey::KEY_R, PolicyErrorCodes::DeniedChord));
+        assert_eq!(policy.check(&parse_notation("R L⊞↓ E L⊞↑").unwrap(), us()), Ok(()));
+    }
+
+    #[test]
+    fn test_denied_and_allowed_keys() {
+        let policy = KeyPolicy { denied_keys: vec![VirtualKey::ESCAPE], ..KeyPolicy::default() };
+        assert_eq!(policy.check(&parse_notation("A ⎋").unwrap(), us()), error(1, VirtualKey::ESCAPE, PolicyErrorCodes::DeniedKey));
+
+        let policy = KeyPolicy { allowed_keys: Some(vec![VirtualKey::KEY_A, VirtualKey::SHIFT]), ..KeyPolicy::default() };
+        assert_eq!(policy.check_keystrokes(&crate::to_keystrokes_new("aAa").unwrap(), us()), Ok(()));
+        assert_eq!(policy.check_keystrokes(&crate::to_keystrokes_new("ab").unwrap(), us()), error(1, VirtualKey::KEY_B, PolicyErrorCodes::DeniedKey));
+        assert_eq!(policy.check(&parse_notation("L⇧↓ A L⇧↑ R⇧↓ A R⇧↑").unwrap(), us()), Ok(()));
+
+        let policy = KeyPolicy { denied_keys: vec![VirtualKey::LWIN, VirtualKey::MENU], ..KeyPolicy::default() };
+        assert_eq!(policy.check(&parse_notation("A R⊞↓ R R⊞↑").unwrap(), us()), error(1, VirtualKey::RWIN, PolicyErrorCodes::DeniedKey));
+        assert_eq!(policy.check(&parse_notation("L⎇↓ F4 L⎇↑").unwrap(), us()), error(0, VirtualKey::LMENU, PolicyErrorCodes::DeniedKey));
+        assert_eq!(policy.check(&parse_notation("R⎇↓ F4 R⎇↑").unwrap(), us()), error(0, VirtualKey::RMENU, PolicyErrorCodes::DeniedKey));
+    }
+
+    #[test]
+    fn test_max_chord_size_and_allowed_chords() {
+        let keystrokes = parse_notation("⌃↓ ⎇↓ DELETE ⎇↑ ⌃↑").unwrap();
+        let mut policy = KeyPolicy { max_chord_size: 2, ..KeyPolicy::default() };
+        assert_eq!(policy.check(&keystrokes, us()), error(2, VirtualKey::DELETE, PolicyErrorCodes::ChordTooLarge));
+
+        policy.allowed_chords.push(Chord::parse("CONTROL+MENU+DELETE").unwrap());
+        assert_eq!(policy.check(&keystrokes, us()), Ok(()));
+    }
+
+    #[test]
+    fn test_text_only() {
+        let policy = KeyPolicy::text_only();
+
+        assert_eq!(policy.check_keystrokes(&crate::to_keystrokes_new("Hello, World! ~{}").unwrap(), us()), Ok(()));
+        assert_eq!(policy.check(&parse_notation("H I ↵").unwrap(), us()), error(2, VirtualKey::RETURN, PolicyErrorCodes::NotPrintable));
+        assert_eq!(policy.check(&parse_notation("L⊞↓ R L⊞↑").unwrap(), us()), error(0, VirtualKey::LWIN, PolicyErrorCodes::NotPrintable));
+        assert_eq!(policy.check(&parse_notation("⌃↓ C ⌃↑").unwrap(), us()), error(0, VirtualKey::CONTROL, PolicyErrorCodes::NotPrintable));
+    }
+
+    #[test]
+    fn test_text_only_german() {
+        let policy = KeyPolicy::text_only();
+        let german = KeyboardLayout::german();
+
+        for text in ["Café", "a@b.de", "Grüße, {José}! 5 €"] {
+            let keystrokes = crate::to_keystrokes_with_layout(text, german).unwrap();
+            assert_eq!(policy.check_keystrokes(&keystrokes, german), Ok(()), "{text}");
+        }
+        // Ctrl is accepted for AltGr, but not to send shortcuts
+        assert_eq!(policy.check(&parse_notation("⌃↓ C ⌃↑").unwrap(), german), error(1, VirtualKey::KEY_C, PolicyErrorCodes::NotPrintable));
+        assert_eq!(policy.check(&parse_notation("⌃↓ ⎇↓ DELETE ⎇↑ ⌃↑").unwrap(), german), error(2, VirtualKey::DELETE, PolicyErrorCodes::NotPrintable));
+        assert_eq!(policy.check(&parse_notation("L⊞↓ R L⊞↑").unwrap(), german), error(0, VirtualKey::LWIN, PolicyErrorCodes::NotPrintable));
+    }
+
+    #[test]
+    fn test_chord_parse_and_display() {
+        let chord = Chord::parse("VK_RMENU + f4").unwrap();
+
+        assert_eq!(chord.keys(), [VirtualKey::MENU, VirtualKey::F4]);
+        assert_eq!(chord.to_string(), "VK_MENU+VK_F4");
+        assert_eq!(Chord::parse("CONTROL+NOPE"), None);
+    }
+}
@@ -0,0 +1,418 @@
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+use crate::keystroke::Keystroke;
+use crate::virtual_key::VirtualKey;
+
+/*
+Recording layout, all integers are little endian or LEB128 varints:
+
+    magic       4 bytes  "VKSQ"
+    version     1 byte
+    layout id   1 byte length + UTF-8 bytes
+    records     ...
+    end         1 byte   0xFF
+
+Each record is a tag byte followed by the virtual key:
+
+    tag bits 0-1  action (0 down, 1 up, 2 tap, 3 repeat since version 2)
+    tag bit  2    a varint run count follows, the event repeats that many times, at most MAX_RUN
+    tag bit  3    a varint delay in microseconds follows, applied after every event in the run
+    vk            1 byte
+ */
+
+/// Identifies a keystroke recording.
+pub const MAGIC: [u8; 4] = *b"VKSQ";
+/// The version written by [`RecordingWriter`] and the newest understood by [`RecordingReader`].
+pub const VERSION: u8 = 2;
+/// The longest run of one event a record may hold. Longer runs are split over several records, so a
+/// few bytes of input never expand into more events than this.
+pub const MAX_RUN: u64 = 1 << 16;
+
+const ACTION_MASK: u8 = 0b0011;
+const ACTION_DOWN: u8 = 0;
+const ACTION_UP: u8 = 1;
+const ACTION_TAP: u8 = 2;
+const ACTION_REPEAT: u8 = 3;
+const HAS_RUN: u8 = 0b0100;
+const HAS_DELAY: u8 = 0b1000;
+const END: u8 = 0xFF;
+
+/// A key event along with how long to wait after sending it.
+pub type TimedKeystroke = (Keystroke, Option<Duration>);
+
+/// Errors that can occur when reading or writing a recording.
+#[derive(Debug)]
+pub enum RecordingError {
+    /// The underlying reader or writer failed.
+    Io(io::Error),
+    /// The data does not start with [`MAGIC`].
+    BadMagic,
+    /// The recording was written by a newer version of this format.
+    UnsupportedVersion(u8),
+    /// The layout identifier is longer than 255 bytes or is not valid UTF-8.
+    InvalidLayoutId,
+    /// A record tag has unknown bits set.
+    InvalidTag(u8),
+    /// A varint does not fit in 64 bits.
+    InvalidVarint,
+    /// A record repeats its event more than [`MAX_RUN`] times.
+    RunTooLong(u64),
+    /// The data ended before the end marker.
+    Truncated,
+}
+
+impl From<io::Error> for RecordingError {
+    fn from(err: io::Error) -> Self {
+        if err.kind() == io::ErrorKind::UnexpectedEof {
+            RecordingError::Truncated
+        } else {
+            RecordingError::Io(err)
+        }
+    }
+}
+
+/// Writes a recording, coalescing identical consecutive events into a single run.
+///
+/// Call [`RecordingWriter::finish`] once done, a recording without its end marker is reported as
+/// truncated when read back.
+pub struct RecordingWriter<W: Write> {
+    writer: W,
+    pending: Option<(TimedKeystroke, u64)>,
+}
+
+impl<W: Write> RecordingWriter<W> {
+    /// Writes the header. `layout_id` names the layout the keystrokes were generated for, such as
+    /// `"00000409"`, and must be at most 255 bytes.
+    pub fn new(mut writer: W, layout_id: &str) -> Result<RecordingWriter<W>, RecordingError> {
+        let len = u8::try_from(layout_id.len()).map_err(|_| RecordingError::InvalidLayoutId)?;
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&[VERSION, len])?;
+        writer.write_all(layout_id.as_bytes())?;
+        Ok(RecordingWriter { writer, pending: None })
+    }
+
+    pub fn write(&mut self, keystroke: Keystroke, delay: Option<Duration>) -> Result<(), RecordingError> {
+        let event = (keystroke, delay);
+        match &mut self.pending {
+            Some((pending, count)) if *pending == event && *count < MAX_RUN => *count += 1,
+            _ => {
+                self.flush_pending()?;
+                self.pending = Some((event, 1));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn write_all(&mut self, keystrokes: &[TimedKeystroke]) -> Result<(), RecordingError> {
+        for &(keystroke, delay) in keystrokes {
+            self.write(keystroke, delay)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the end marker and hands back the underlying writer.
+    pub fn finish(mut self) -> Result<W, RecordingError> {
+        self.flush_pending()?;
+        self.writer.write_all(&[END])?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn flush_pending(&mut self) -> Result<(), RecordingError> {
+        let Some(((keystroke, delay), count)) = self.pending.take() else {
+            return Ok(())
+        };
+
+        let (action, vk) = match keystroke {
+            Keystroke::Down(vk) => (ACTION_DOWN, vk),
+            Keystroke::Up(vk) => (ACTION_UP, vk),
+            Keystroke::Tap(vk) => (ACTION_TAP, vk),
+            Keystroke::Repeat(vk) => (ACTION_REPEAT, vk),
+        };
+        let mut tag = action;
+        if count > 1 { tag |= HAS_RUN; }
+        if delay.is_some() { tag |= HAS_DELAY; }
+
+        self.writer.write_all(&[tag, vk.0])?;
+        if count > 1 {
+            write_varint(&mut self.writer, count)?;
+        }
+        if let Some(delay) = delay {
+            write_varint(&mut self.writer, u64::try_from(delay.as_micros()).unwrap_or(u64::MAX))?;
+        }
+        Ok(())
+    }
+}
+
+/// Streams the events out of a recording, expanding runs back into individual events.
+pub struct RecordingReader<R: Read> {
+    reader: R,
+    version: u8,
+    layout_id: String,
+    run: Option<(TimedKeystroke, u64)>,
+    is_done: bool,
+}
+
+impl<R: Read> RecordingReader<R> {
+    /// Reads and validates the header.
+    pub fn new(mut reader: R) -> Result<RecordingReader<R>, RecordingError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(RecordingError::BadMagic)
+        }
+
+        let [version, len] = read_array(&mut reader)?;
+        if version == 0 || version > VERSION {
+            return Err(RecordingError::UnsupportedVersion(version))
+        }
+
+        let mut layout_id = vec![0; len as usize];
+        reader.read_exact(&mut layout_id)?;
+        let layout_id = String::from_utf8(layout_id).map_err(|_| RecordingError::InvalidLayoutId)?;
+
+        Ok(RecordingReader { reader, version, layout_id, run: None, is_done: false })
+    }
+
+    /// The format version the recording was written with.
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// The layout the keystrokes were generated for.
+    pub fn layout_id(&self) -> &str {
+        &self.layout_id
+    }
+
+    fn read_record(&mut self) -> Result<Option<(TimedKeystroke, u64)>, RecordingError> {
+        let [tag] = read_array(&mut self.reader)?;
+        if tag == END {
+            return Ok(None)
+        }
+        if tag & !(ACTION_MASK | HAS_RUN | HAS_DELAY) != 0 {
+            return Err(RecordingError::InvalidTag(tag))
+        }
+
+        let [vk] = read_array(&mut self.reader)?;
+        let vk = VirtualKey(vk);
+        let keystroke = match tag & ACTION_MASK {
+            ACTION_DOWN => Keystroke::Down(vk),
+            ACTION_UP => Keystroke::Up(vk),
+            ACTION_TAP => Keystroke::Tap(vk),
+            ACTION_REPEAT if self.version >= 2 => Keystroke::Repeat(vk),
+            _ => return Err(RecordingError::InvalidTag(tag)),
+        };
+        let count = if tag & HAS_RUN != 0 { read_varint(&mut self.reader)? } else { 1 };
+        if count > MAX_RUN {
+            return Err(RecordingError::RunTooLong(count))
+        }
+        let delay = if tag & HAS_DELAY != 0 {
+            Some(Duration::from_micros(read_varint(&mut self.reader)?))
+        } else {
+            None
+        };
+
+        Ok(Some(((keystroke, delay), count)))
+    }
+}
+
+impl<R: Read> Iterator for RecordingReader<R> {
+    type Item = Result<TimedKeystroke, RecordingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((event, count)) = &mut self.run && *count > 0 {
+                *count -= 1;
+                return Some(Ok(*event))
+            }
+            if self.is_done {
+                return None
+            }
+
+            match self.read_record() {
+                Ok(Some(run)) => self.run = Some(run),
+                Ok(None) => self.is_done = true,
+                Err(err) => {
+                    self.is_done = true;
+                    return Some(Err(err))
+                }
+            }
+        }
+    }
+}
+
+/// Encodes a whole recording into memory.
+pub fn encode_recording(layout_id: &str, keystrokes: &[TimedKeystroke]) -> Result<Vec<u8>, RecordingError> {
+    let mut writer = RecordingWriter::new(Vec::new(), layout_id)?;
+    writer.write_all(keystrokes)?;
+    writer.finish()
+}
+
+/// Decodes a whole recording from memory, returning its layout identifier and events.
+pub fn decode_recording(bytes: &[u8]) -> Result<(String, Vec<TimedKeystroke>), RecordingError> {
+    let mut reader = RecordingReader::new(bytes)?;
+    let keystrokes = reader.by_ref().collect::<Result<_, _>>()?;
+    Ok((reader.layout_id, keystrokes))
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> Result<[u8; N], RecordingError> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn write_varint(writer: &mut impl Write, mut value: u64) -> Result<(), RecordingError> {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            writer.write_all(&[byte])?;
+            return Ok(())
+        }
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint(reader: &mut impl Read) -> Result<u64, RecordingError> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let [byte] = read_array(reader)?;
+        let bits = u64::from(byte & 0x7F);
+        if bits << shift >> shift != bits {
+            return Err(RecordingError::InvalidVarint)
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value)
+        }
+    }
+    Err(RecordingError::InvalidVarint)
+}
+
+
+
+/* ### --- UNIT TEST --- ### */
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn untimed(text: &str) -> Vec<TimedKeystroke> {
+        crate::decode_keystrokes(&crate::to_keystrokes_new(text).unwrap())
+            .into_iter()
+            .map(|keystroke| (keystroke, None))
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let events = untimed("Hello, World!");
+        let bytes = encode_recording("00000409", &events).unwrap();
+
+        assert_eq!(decode_recording(&bytes).unwrap(), ("00000409".to_string(), events));
+    }
+
+    #[test]
+    fn test_round_trip_with_delays() {
+        let events = vec![
+            (Keystroke::Down(VirtualKey::SHIFT), Some(Duration::from_millis(5))),
+            (Keystroke::Tap(VirtualKey::KEY_A), Some(Duration::from_millis(50))),
+            (Keystroke::Tap(VirtualKey::KEY_A), Some(Duration::from_millis(50))),
+            (Keystroke::Up(VirtualKey::SHIFT), None),
+        ];
+        let bytes = encode_recording("", &events).unwrap();
+
+        assert_eq!(decode_recording(&bytes).unwrap().1, events);
+    }
+
+    #[test]
+    fn test_header() {
+        let bytes = encode_recording("US", &[]).unwrap();
+
+        assert_eq!(bytes, b"VKSQ\x02\x02US\xFF");
+    }
+
+    #[test]
+    fn test_runs_are_coalesced() {
+        let events = untimed(&"a".repeat(1000));
+        let bytes = encode_recording("", &events).unwrap();
+
+        // header, one tag + vk + two byte varint, end marker
+        assert_eq!(bytes.len(), 6 + 4 + 1);
+        assert_eq!(decode_recording(&bytes).unwrap().1.len(), 1000);
+    }
+
+    #[test]
+    fn test_long_runs_are_split() {
+        let events = untimed(&"a".repeat(MAX_RUN as usize * 2 + 1));
+        let bytes = encode_recording("", &events).unwrap();
+
+        assert_eq!(decode_recording(&bytes).unwrap().1, events);
+    }
+
+    #[test]
+    fn test_run_too_long() {
+        // A tap of A repeated u64::MAX times
+        let bytes = b"VKSQ\x02\x00\x06\x41\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\x01\xFF";
+
+        assert!(matches!(decode_recording(bytes), Err(RecordingError::RunTooLong(u64::MAX))));
+        assert!(matches!(decode_recording(b"VKSQ\x02\x00\x06\x41\x81\x80\x04\xFF"), Err(RecordingError::RunTooLong(0x10001))));
+    }
+
+    #[test]
+    fn test_streaming_reader() {
+        let bytes = encode_recording("US", &untimed("Hi")).unwrap();
+        let mut reader = RecordingReader::new(&bytes[..]).unwrap();
+
+        assert_eq!(reader.version(), VERSION);
+        assert_eq!(reader.layout_id(), "US");
+        assert_eq!(reader.next().unwrap().unwrap(), (Keystroke::Down(VirtualKey::SHIFT), None));
+        assert_eq!(reader.count(), 3);
+    }
+
+    #[test]
+    fn test_bad_magic() {
+        assert!(matches!(decode_recording(b"NOPE\x01\x00\xFF"), Err(RecordingError::BadMagic)));
+    }
+
+    #[test]
+    fn test_newer_version_is_rejected() {
+        assert!(matches!(decode_recording(b"VKSQ\x03\x00\xFF"), Err(RecordingError::UnsupportedVersion(3))));
+    }
+
+    #[test]
+    fn test_missing_end_is_truncated() {
+        let bytes = encode_recording("US", &untimed("Hi")).unwrap();
+
+        assert!(matches!(decode_recording(&bytes[..bytes.len() - 1]), Err(RecordingError::Truncated)));
+    }
+
+    #[test]
+    fn test_repeats_need_version_2() {
+        assert_eq!(decode_recording(b"VKSQ\x02\x00\x03\x08\xFF").unwrap().1, vec![(Keystroke::Repeat(VirtualKey::BACK), None)]);
+        assert!(matches!(decode_recording(b"VKSQ\x01\x00\x03\x08\xFF"), Err(RecordingError::InvalidTag(0x03))));
+    }
+
+    #[test]
+    fn test_invalid_tag() {
+        assert!(matches!(decode_recording(b"VKSQ\x01\x00\x03\x41\xFF"), Err(RecordingError::InvalidTag(0x03))));
+        assert!(matches!(decode_recording(b"VKSQ\x01\x00\x10\x41\xFF"), Err(RecordingError::InvalidTag(0x10))));
+    }
+
+    #[test]
+    fn test_layout_id_too_long() {
+        assert!(matches!(encode_recording(&"x".repeat(256), &[]), Err(RecordingError::InvalidLayoutId)));
+    }
+
+    #[test]
+    fn test_varint_round_trip() {
+        for value in [0, 1, 127, 128, 300, u64::from(u32::MAX), u64::MAX] {
+            let mut bytes = Vec::new();
+            write_varint(&mut bytes, value).unwrap();
+            assert_eq!(read_varint(&mut &bytes[..]).unwrap(), value);
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use std::sync::OnceLock;
+
+use crate::layout::{to_keystrokes_with_layout, KeyboardLayout};
+use crate::{ErrorCodes, KeyError};
+
+/// A layout in a [`LayoutRegistry`] along with the identifiers it is known by.
+#[derive(Clone, Debug)]
+pub struct RegisteredLayout {
+    klid: u32,
+    locale: String,
+    layout: KeyboardLayout,
+}
+
+impl RegisteredLayout {
+    /// The keyboard layout identifier, e.g. `0x00000407` for German.
+    pub fn klid(&self) -> u32 {
+        self.klid
+    }
+
+    /// The KLID the way Windows writes it, as eight uppercase hex digits such as `"00000407"`.
+    pub fn klid_string(&self) -> String {
+        format!("{:08X}", self.klid)
+    }
+
+    /// The BCP-47 tag of the locale the layout belongs to, e.g. `"de-DE"`.
+    pub fn locale(&self) -> &str {
+        &self.locale
+    }
+
+    pub fn layout(&self) -> &KeyboardLayout {
+        &self.layout
+    }
+}
+
+/// Keyboard layouts keyed by the identifiers Windows and configuration files use for them: KLID
+/// strings such as `"00000407"`, `HKL` values from `GetKeyboardLayout` and BCP-47 locale tags such as
+/// `"de-DE"`.
+///
+/// Lookups only match what was registered, since typing with a layout that is merely close, like
+/// German for Swiss German, swaps or loses characters. The one exception is a bare language tag such
+/// as `"de"`, which finds the default layout of the language: the first layout registered for it
+/// until [`LayoutRegistry::set_language_default`] picks another.
+#[derive(Clone, Debug)]
+pub struct LayoutRegistry {
+    layouts: Vec<RegisteredLayout>,
+    language_defaults: Vec<(String, u32)>,
+}
+
+impl Default for LayoutRegistry {
+    /// A registry with the built-in layouts.
+    fn default() -> Self {
+        let mut registry = LayoutRegistry::empty();
+        registry.register(0x00000409, "en-US", KeyboardLayout::us().clone());
+        registry.register(0x00000407, "de-DE", KeyboardLayout::german().clone());
+        registry
+    }
+}
+
+impl LayoutRegistry {
+    /// A registry with the built-in layouts, see [`LayoutRegistry::builtin`].
+    pub fn new() -> LayoutRegistry {
+        LayoutRegistry::default()
+    }
+
+    /// A registry without any layouts.
+    pub fn empty() -> LayoutRegistry {
+        LayoutRegistry { layouts: Vec::new(), language_defaults: Vec::new() }
+    }
+
+    /// A shared registry with the built-in layouts: US (`00000409`, `en-US`) and German
+    /// (`00000407`, `de-DE`).
+    pub fn builtin() -> &'static LayoutRegistry {
+        static BUILTIN: OnceLock<LayoutRegistry> = OnceLock::new();
+        BUILTIN.get_or_init(LayoutRegistry::default)
+    }
+
+    /// Adds `layout` under `klid` and the BCP-47 tag `locale`, replacing any layout registered with
+    /// the same KLID.
+    pub fn register(&mut self, klid: u32, locale: impl Into<String>, layout: KeyboardLayout) {
+        let entry = RegisteredLayout { klid, locale: locale.into(), layout };
+        match self.layouts.iter_mut().find(|registered| registered.klid == klid) {
+            Some(registered) => *registered = entry,
+            None => self.layouts.push(entry),
+        }
+    }
+
+    /// Makes the layout registered under `klid` the one the bare language tag `language`, e.g.
+    /// `"de"`, finds.
+    pub fn set_language_default(&mut self, language: &str, klid: u32) {
+        self.language_defaults.retain(|(known, _)| !known.eq_ignore_ascii_case(language));
+        self.language_defaults.push((language.to_ascii_lowercase(), klid));
+    }
+
+    /// Every registered layout, in the order they were registered.
+    pub fn layouts(&self) -> &[RegisteredLayout] {
+        &self.layouts
+    }
+
+    /// Finds the layout registered under exactly `klid`.
+    pub fn by_klid(&self, klid: u32) -> Option<&RegisteredLayout> {
+        self.layouts.iter().find(|registered| registered.klid == klid)
+    }
+
+    /// Finds the layout for an input locale handle. The high word of an `HKL` names the layout and is
+    /// looked up like the KLID with the same low word, so `0x08070807` finds what `"00000807"` finds.
+    /// Variants Windows identifies only through the registry (`0xFxxx`) are never found, their KLID
+    /// is not part of the handle.
+    pub fn by_hkl(&self, hkl: usize) -> Option<&RegisteredLayout> {
+        let device = ((hkl >> 16) & 0xFFFF) as u16;
+        if device & 0xF000 == 0xF000 {
+            return None
+        }
+        self.by_klid(device as u32)
+    }
+
+    /// Finds the layout registered for a BCP-47 tag, or the default layout of a bare language tag
+    /// such as `"de"`. Tags are compared ignoring ASCII case and `_` is accepted in place of `-`.
+    pub fn by_locale(&self, tag: &str) -> Option<&RegisteredLayout> {
+        let tag = tag.replace('_', "-");
+        if let Some(registered) = self.layouts.iter().find(|registered| registered.locale.eq_ignore_ascii_case(&tag)) {
+            return Some(registered)
+        }
+        if tag.contains('-') {
+            return None
+        }
+
+        self.language_defaults.iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(&tag))
+            .and_then(|&(_, klid)| self.by_klid(klid))
+            .or_else(|| self.layouts.iter().find(|registered| language_of(&registered.locale).eq_ignore_ascii_case(&tag)))
+    }
+
+    /// Finds a layout by any identifier: eight hex digits are a KLID, `0x` followed by hex digits is
+    /// an `HKL` and anything else is a locale tag.
+    pub fn resolve(&self, id: &str) -> Option<&RegisteredLayout> {
+        let id = id.trim();
+        if let Some(hex) = id.strip_prefix("0x").or_else(|| id.strip_prefix("0X")) {
+            return usize::from_str_radix(hex, 16).ok().and_then(|hkl| self.by_hkl(hkl))
+        }
+        if id.len() == 8 && let Ok(klid) = u32::from_str_radix(id, 16) {
+            return self.by_klid(klid)
+        }
+        self.by_locale(id)
+    }
+
+    /// The layout for `id`, see [`LayoutRegistry::resolve`].
+    pub fn get(&self, id: &str) -> Option<&KeyboardLayout> {
+        self.resolve(id).map(RegisteredLayout::layout)
+    }
+}
+
+fn language_of(tag: &str) -> &str {
+    tag.split(['-', '_']).next().unwrap_or_default()
+}
+
+/// Translates `keys` with the layout `registry` has for `layout_id`, such as the ID a machine reports
+/// or the one stored in a recording. See [`LayoutRegistry::resolve`] for the accepted identifiers.
+pub fn to_keystrokes_for(keys: &str, registry: &LayoutRegistry, layout_id: &str) -> Result<Vec<u8>, KeyError> {
+    let layout = registry.get(layout_id).ok_or(KeyError { byte: 0, error_code: ErrorCodes::UnknownLayout })?;
+    to_keystrokes_with_layout(keys, layout)
+}
+
+
+
+/* ### --- UNIT TEST --- ### */
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::{KeyMapping, ShiftState};
+    use crate::virtual_key::VirtualKey;
+
+    fn name(registered: Option<&RegisteredLayout>) -> Option<&str> {
+        registered.map(|registered| registered.layout().name())
+    }
+
+    #[test]
+    fn test_identifiers() {
+        let registry = LayoutRegistry::builtin();
+
+        assert_eq!(name(registry.resolve("00000407")), Some("German"));
+        assert_eq!(name(registry.resolve("0x04090409")), Some("US"));
+        assert_eq!(name(registry.resolve("de-DE")), Some("German"));
+        assert_eq!(name(registry.resolve("EN_us")), Some("US"));
+        assert_eq!(registry.resolve("00000407").unwrap().klid_string(), "00000407");
+        assert_eq!(name(registry.resolve("00000409 ")), Some("US"));
+    }
+
+    #[test]
+    fn test_fallbacks() {
+        let registry = LayoutRegistry::builtin();
+
+        assert_eq!(name(registry.by_locale("de")), Some("German"));
+        assert_eq!(name(registry.by_locale("DE")), Some("German"));
+        assert_eq!(name(registry.by_locale("de-CH")), None);
+        assert_eq!(name(registry.by_locale("de-AT")), None);
+        assert_eq!(name(registry.by_locale("fr")), None);
+        assert_eq!(name(registry.resolve("0000040C")), None);
+    }
+
+    #[test]
+    fn test_hkl_matches_klid() {
+        let registry = LayoutRegistry::builtin();
+
+        // US layout on a German language
+        assert_eq!(name(registry.by_hkl(0x04090407)), Some("US"));
+        assert_eq!(name(registry.resolve("0x08070807")), None);
+        assert_eq!(name(registry.resolve("00000807")), None);
+        // US Dvorak is a registry variant of US English
+        assert_eq!(name(registry.by_hkl(0xF0020409)), None);
+    }
+
+    #[test]
+    fn test_user_layouts() {
+        let swiss = KeyboardLayout::new("Swiss German", vec![
+            KeyMapping { character: 'z', vk: VirtualKey::KEY_Z, shift_state: ShiftState::NONE },
+        ]);
+        let mut registry = LayoutRegistry::new();
+        registry.register(0x00000807, "de-CH", swiss);
+
+        assert_eq!(name(registry.by_locale("de-ch")), Some("Swiss German"));
+        assert_eq!(name(registry.by_hkl(0x08070807)), Some("Swiss German"));
+        assert_eq!(name(registry.by_locale("de-AT")), None);
+        assert_eq!(name(registry.by_locale("de")), Some("German"));
+
+        registry.set_language_default("de", 0x00000807);
+        assert_eq!(name(registry.by_locale("de")), Some("Swiss German"));
+        assert_eq!(name(registry.by_locale("de-DE")), Some("German"));
+    }
+
+    #[test]
+    fn test_register_replaces_klid() {
+        let mut registry = LayoutRegistry::empty();
+        registry.register(0x00000409, "en-US", KeyboardLayout::new("first", Vec::new()));
+        registry.register(0x00000409, "en-US", KeyboardLayout::new("second", Vec::new()));
+
+        assert_eq!(registry.layouts().len(), 1);
+        assert_eq!(name(registry.by_klid(0x00000409)), Some("second"));
+    }
+
+    #[test]
+    fn test_to_keystrokes_for() {
+        let registry = LayoutRegistry::builtin();
+
+        assert_eq!(to_keystrokes_for("Hi!", registry, "en-US"), crate::to_keystrokes_new("Hi!"));
+        assert_eq!(to_keystrokes_for("ß", registry, "00000407").unwrap(), vec![VirtualKey::OEM_4.0]);
+        assert_eq!(to_keystrokes_for("a", registry, "xx-YY"), Err(KeyError { byte: 0, error_code: ErrorCodes::UnknownLayout }));
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::keystroke::{decode_keystrokes, is_modifier, Keystroke};
+use crate::rng::SplitMix64;
+use crate::virtual_key::VirtualKey;
+
+/// A normal distribution of durations in milliseconds.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Latency {
+    pub mean_ms: f64,
+    pub std_dev_ms: f64,
+}
+
+impl Latency {
+    pub const fn new(mean_ms: f64, std_dev_ms: f64) -> Latency {
+        Latency { mean_ms, std_dev_ms }
+    }
+}
+
+/// Errors that can occur when loading a [`RhythmModel`].
+#[derive(Debug)]
+pub enum RhythmError {
+    /// The statistics file could not be read.
+    Io(io::Error),
+    /// The line, counting from 1, is not a valid record.
+    InvalidLine(usize),
+}
+
+impl From<io::Error> for RhythmError {
+    fn from(err: io::Error) -> Self {
+        RhythmError::Io(err)
+    }
+}
+
+/// A seeded model of human typing rhythm built from per-digraph latency statistics.
+///
+/// Statistics are read from a plain text file with one record per line. Fields are separated by
+/// whitespace or commas, keys are virtual key names or codes as accepted by [`VirtualKey::parse`],
+/// times are in milliseconds and `#` starts a comment:
+///
+/// ```text
+/// # kind          keys        mean  std dev
+/// default_latency             120   35
+/// default_hold                90    20
+/// digraph         VK_T VK_H   95    18
+/// hold            VK_SHIFT    140   30
+/// ```
+///
+/// A digraph is the time between one key and the next, a hold is how long a tapped key stays down.
+#[derive(Clone, PartialEq, Debug)]
+pub struct RhythmModel {
+    /// Used for key pairs without a digraph record.
+    pub default_latency: Latency,
+    /// Used for keys without a hold record.
+    pub default_hold: Latency,
+    /// Sampled durations are never shorter than this.
+    pub min_delay: Duration,
+    digraphs: HashMap<(VirtualKey, VirtualKey), Latency>,
+    holds: HashMap<VirtualKey, Latency>,
+}
+
+impl Default for RhythmModel {
+    fn default() -> Self {
+        RhythmModel::new(Latency::new(120.0, 35.0), Latency::new(90.0, 20.0))
+    }
+}
+
+impl RhythmModel {
+    pub fn new(default_latency: Latency, default_hold: Latency) -> RhythmModel {
+        RhythmModel {
+            default_latency,
+            default_hold,
+            min_delay: Duration::from_millis(1),
+            digraphs: HashMap::new(),
+            holds: HashMap::new(),
+        }
+    }
+
+    /// Loads statistics from a local file, see [`RhythmModel`] for the format.
+    pub fn load(path: impl AsRef<Path>) -> Result<RhythmModel, RhythmError> {
+        RhythmModel::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parses statistics in the format described on [`RhythmModel`].
+    pub fn parse(text: &str) -> Result<RhythmModel, RhythmError> {
+        let mut model = RhythmModel::default();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let fields: Vec<&str> = line.split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|field| !field.is_empty())
+                .collect();
+            let invalid = || RhythmError::InvalidLine(i + 1);
+            let key = |field: &str| VirtualKey::parse(field).ok_or_else(invalid);
+            let latency = |mean: &str, std_dev: &str| -> Result<Latency, RhythmError> {
+                let mean = mean.parse::<f64>().map_err(|_| invalid())?;
+                let std_dev = std_dev.parse::<f64>().map_err(|_| invalid())?;
+                if !mean.is_finite() || !std_dev.is_finite() || mean < 0.0 || std_dev < 0.0 {
+                    return Err(invalid())
+                }
+                Ok(Latency::new(mean, std_dev))
+            };
+
+            match fields[..] {
+                [] => {}
+                ["default_latency", mean, std_dev] => model.default_latency = latency(mean, std_dev)?,
+                ["default_hold", mean, std_dev] => model.default_hold = latency(mean, std_dev)?,
+                ["digraph", first, second, mean, std_dev] => {
+                    model.set_digraph(key(first)?, key(second)?, latency(mean, std_dev)?);
+                }
+                ["hold", vk, mean, std_dev] => model.set_hold(key(vk)?, latency(mean, std_dev)?),
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(model)
+    }
+
+    pub fn set_digraph(&mut self, first: VirtualKey, second: VirtualKey, latency: Latency) {
+        self.digraphs.insert((first, second), latency);
+    }
+
+    pub fn set_hold(&mut self, vk: VirtualKey, hold: Latency) {
+        self.holds.insert(vk, hold);
+    }
+
+    pub fn digraph(&self, first: VirtualKey, second: VirtualKey) -> Latency {
+        self.digraphs.get(&(first, second)).copied().unwrap_or(self.default_latency)
+    }
+
+    pub fn hold(&self, vk: VirtualKey) -> Latency {
+        self.holds.get(&vk).copied().unwrap_or(self.default_hold)
+    }
+
+    /// Assigns a sampled delay to every event. Taps are split into a press and a release so the
+    /// hold time can be applied, the delay after any other event is the latency from its key to the
+    /// key of the next event. The same seed always gives the same schedule.
+    pub fn schedule(&self, keystrokes: &[Keystroke], seed: u64) -> Vec<(Keystroke, Duration)> {
+        let mut rng = SplitMix64::new(seed);
+
+        let mut events = Vec::with_capacity(keystrokes.len() * 2);
+        for &keystroke in keystrokes {
+            match keystroke {
+                Keystroke::Tap(vk) => {
+                    events.push((Keystroke::Down(vk), true));
+                    events.push((Keystroke::Up(vk), false));
+                }
+                _ => events.push((keystroke, false)),
+            }
+        }
+
+        let mut timed = Vec::with_capacity(events.len());
+        for (i, &(keystroke, is_tap_press)) in events.iter().enumerate() {
+            let vk = keystroke.vk();
+            let latency = if is_tap_press && !is_modifier(vk) {
+                self.hold(vk)
+            } else if let Some(&(next, _)) = events.get(i + 1) {
+                self.digraph(vk, next.vk())
+            } else {
+                timed.push((keystroke, Duration::ZERO));
+                continue
+            };
+            timed.push((keystroke, self.sample(&mut rng, latency)));
+        }
+
+        timed
+    }
+
+    /// Assigns a sampled delay to every event in the flat output of [`crate::to_keystrokes_new`].
+    pub fn schedule_keystrokes(&self, keystrokes: &[u8], seed: u64) -> Vec<(Keystroke, Duration)> {
+        self.schedule(&decode_keystrokes(keystrokes), seed)
+    }
+
+    fn sample(&self, rng: &mut SplitMix64, latency: Latency) -> Duration {
+        let ms = rng.next_normal(latency.mean_ms, latency.std_dev_ms).max(0.0);
+        // Means too large for a duration wait as long as possible instead
+        Duration::try_from_secs_f64(ms / 1000.0).unwrap_or(Duration::MAX).max(self.min_delay)
+    }
+}
+
+
+
+/* ### --- UNIT TEST --- ### */
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATS: &str = "
+        # kind          keys        mean  std dev
+        default_latency             100   0
+        default_hold                50,   0
+        digraph         VK_H, E     30    0   # fast pair
+        hold            VK_E        10    0
+    ";
+
+    #[test]
+    fn test_parse() {
+        let model = RhythmModel::parse(STATS).unwrap();
+
+        assert_eq!(model.default_latency, Latency::new(100.0, 0.0));
+        assert_eq!(model.digraph(VirtualKey::KEY_H, VirtualKey::KEY_E), Latency::new(30.0, 0.0));
+        assert_eq!(model.digraph(VirtualKey::KEY_E, VirtualKey::KEY_H), Latency::new(100.0, 0.0));
+        assert_eq!(model.hold(VirtualKey::KEY_E), Latency::new(10.0, 0.0));
+        assert_eq!(model.hold(VirtualKey::KEY_H), Latency::new(50.0, 0.0));
+    }
+
+    #[test]
+    fn test_parse_errors_report_line() {
+        assert!(matches!(RhythmModel::parse("hold VK_A 1 2\nhold VK_NOPE 1 2"), Err(RhythmError::InvalidLine(2))));
+        assert!(matches!(RhythmModel::parse("digraph VK_A VK_B -1 2"), Err(RhythmError::InvalidLine(1))));
+        assert!(matches!(RhythmModel::parse("hold VK_A 1"), Err(RhythmError::InvalidLine(1))));
+        assert!(matches!(RhythmModel::parse("unknown 1 2"), Err(RhythmError::InvalidLine(1))));
+    }
+
+    #[test]
+    fn test_load_missing_file() {
+        assert!(matches!(RhythmModel::load("/does/not/exist.txt"), Err(RhythmError::Io(_))));
+    }
+
+    #[test]
+    fn test_schedule_uses_digraphs_and_holds() {
+        let model = RhythmModel::parse(STATS).unwrap();
+        let timed = model.schedule_keystrokes(&crate::to_keystrokes_new("he").unwrap(), 0);
+
+        assert_eq!(timed, vec![
+            (Keystroke::Down(VirtualKey::KEY_H), Duration::from_millis(50)),
+            (Keystroke::Up(VirtualKey::KEY_H), Duration::from_millis(30)),
+            (Keystroke::Down(VirtualKey::KEY_E), Duration::from_millis(10)),
+            (Keystroke::Up(VirtualKey::KEY_E), Duration::ZERO),
+        ]);
+    }
+
+    #[test]
+    fn test_same_seed_same_schedule() {
+        let model = RhythmModel::default();
+        let strokes = crate::to_keystrokes_new("The quick brown fox.").unwrap();
+
+        assert_eq!(model.schedule_keystrokes(&strokes, 42), model.schedule_keystrokes(&strokes, 42));
+        assert_ne!(model.schedule_keystrokes(&strokes, 42), model.schedule_keystrokes(&strokes, 43));
+    }
+
+    #[test]
+    fn test_min_delay() {
+        let model = RhythmModel {
+            min_delay: Duration::from_millis(20),
+            ..RhythmModel::new(Latency::new(0.0, 50.0), Latency::new(0.0, 50.0))
+        };
+        let timed = model.schedule_keystrokes(&crate::to_keystrokes_new("Hello World").unwrap(), 7);
+
+        assert!(timed[..timed.len() - 1].iter().all(|&(_, delay)| delay >= Duration::from_millis(20)));
+    }
+
+    #[test]
+    fn test_huge_latency_is_clamped() {
+        let model = RhythmModel::parse("default_latency 1e300 0\ndefault_hold 1e300 0").unwrap();
+        let timed = model.schedule_keystrokes(&crate::to_keystrokes_new("ab").unwrap(), 0);
+
+        assert_eq!(timed[0], (Keystroke::Down(VirtualKey::KEY_A), Duration::MAX));
+    }
+
+    #[test]
+    fn test_modifiers_are_not_split() {
+        let timed = RhythmModel::default().schedule_keystrokes(&crate::to_keystrokes_new("A").unwrap(), 1);
+        let events: Vec<Keystroke> = timed.into_iter().map(|(keystroke, _)| keystroke).collect();
+
+        assert_eq!(events, vec![
+            Keystroke::Down(VirtualKey::SHIFT),
+            Keystroke::Down(VirtualKey::KEY_A),
+            Keystroke::Up(VirtualKey::KEY_A),
+            Keystroke::Up(VirtualKey::SHIFT),
+        ]);
+    }
+}
@@ -0,0 +1,65 @@
+/// A small seeded random number generator (SplitMix64). Seeds are part of the public API of the
+/// timing and typo models, so the sequence must never change between versions.
+/// See: https://prng.di.unimi.it/splitmix64.c
+pub(crate) struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> SplitMix64 {
+        SplitMix64(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A uniform value in `[0, 1)`.
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A uniform index in `[0, len)`. `len` must not be zero.
+    pub(crate) fn next_index(&mut self, len: usize) -> usize {
+        (self.next_f64() * len as f64) as usize % len
+    }
+
+    /// A normally distributed value using the Box-Muller transform.
+    pub(crate) fn next_normal(&mut self, mean: f64, std_dev: f64) -> f64 {
+        let u1 = 1.0 - self.next_f64(); // (0, 1] so the log is finite
+        let u2 = self.next_f64();
+        mean + std_dev * (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+    }
+}
+
+
+
+/* ### --- UNIT TEST --- ### */
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reference_values() {
+        // First outputs of the reference implementation seeded with 1234567
+        let mut rng = SplitMix64::new(1234567);
+        assert_eq!(rng.next_u64(), 6457827717110365317);
+        assert_eq!(rng.next_u64(), 3203168211198807973);
+    }
+
+    #[test]
+    fn test_ranges() {
+        let mut rng = SplitMix64::new(7);
+        for _ in 0..1000 {
+            let value = rng.next_f64();
+            assert!((0.0..1.0).contains(&value));
+            assert!(rng.next_index(3) < 3);
+            assert!(rng.next_normal(0.0, 1.0).is_finite());
+        }
+    }
+}
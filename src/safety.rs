@@ -0,0 +1,279 @@
+use std::time::Duration;
+
+use crate::keystroke::{decode_keystrokes, Keystroke};
+use crate::sender::KeystrokeSender;
+use crate::virtual_key::VirtualKey;
+
+/// Every modifier key, released by [`release_all_modifiers`].
+const MODIFIERS: [VirtualKey; 11] = [
+    VirtualKey::SHIFT, VirtualKey::LSHIFT, VirtualKey::RSHIFT,
+    VirtualKey::CONTROL, VirtualKey::LCONTROL, VirtualKey::RCONTROL,
+    VirtualKey::MENU, VirtualKey::LMENU, VirtualKey::RMENU,
+    VirtualKey::LWIN, VirtualKey::RWIN,
+];
+
+/// Tracks which keys are held down while events are sent.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct HeldKeys {
+    keys: Vec<VirtualKey>,
+}
+
+impl HeldKeys {
+    pub fn new() -> HeldKeys {
+        HeldKeys::default()
+    }
+
+    /// Updates the held keys after `keystroke` was sent.
+    pub fn track(&mut self, keystroke: Keystroke) {
+        match keystroke {
+            Keystroke::Down(vk) | Keystroke::Repeat(vk) => {
+                if !self.keys.contains(&vk) {
+                    self.keys.push(vk);
+                }
+            }
+            Keystroke::Up(vk) => self.keys.retain(|&held| held != vk),
+            Keystroke::Tap(_) => {}
+        }
+    }
+
+    /// Keys currently held down, in the order they were pressed.
+    pub fn keys(&self) -> &[VirtualKey] {
+        &self.keys
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// The key-ups that release every held key, most recently pressed first.
+    pub fn release(&self) -> Vec<Keystroke> {
+        self.keys.iter().rev().map(|&vk| Keystroke::Up(vk)).collect()
+    }
+}
+
+/// The key-ups needed to release every key left held after sending `sent`, most recently pressed
+/// first.
+pub fn cleanup(sent: &[Keystroke]) -> Vec<Keystroke> {
+    let mut held = HeldKeys::new();
+    for &keystroke in sent {
+        held.track(keystroke);
+    }
+    held.release()
+}
+
+/// The key-ups needed after sending only the first `sent` bytes of the flat output of
+/// [`crate::to_keystrokes_new`], for example because sending was cancelled.
+pub fn cleanup_keystrokes(keystrokes: &[u8], sent: usize) -> Vec<Keystroke> {
+    cleanup(&decode_keystrokes(&keystrokes[..sent.min(keystrokes.len())]))
+}
+
+/// A key-up for every modifier, left, right and generic. Sending this first releases modifiers the
+/// user may be physically holding, which would otherwise change what the keystrokes type. Releasing a
+/// key that is not down has no effect.
+pub fn release_all_modifiers() -> Vec<Keystroke> {
+    MODIFIERS.iter().map(|&vk| Keystroke::Up(vk)).collect()
+}
+
+/// Wraps a [`KeystrokeSender`] and releases every key it left held when dropped, so an error, an
+/// early return or a panic partway through a sequence never leaves shift pressed on the target.
+pub struct ReleaseGuard<'a, S: KeystrokeSender> {
+    sender: &'a mut S,
+    held: HeldKeys,
+}
+
+impl<'a, S: KeystrokeSender> ReleaseGuard<'a, S> {
+    pub fn new(sender: &'a mut S) -> ReleaseGuard<'a, S> {
+        ReleaseGuard { sender, held: HeldKeys::new() }
+    }
+
+    /// Like [`ReleaseGuard::new`], first sending [`release_all_modifiers`].
+    pub fn with_preamble(sender: &'a mut S) -> Result<ReleaseGuard<'a, S>, S::Error> {
+        sender.send_batch(&release_all_modifiers())?;
+        Ok(ReleaseGuard::new(sender))
+    }
+
+    /// Keys sent as pressed and not yet released.
+    pub fn held_keys(&self) -> &[VirtualKey] {
+        self.held.keys()
+    }
+
+    /// Releases every held key now rather than on drop. When sending fails the keys stay tracked, so
+    /// dropping the guard tries again.
+    pub fn release(&mut self) -> Result<(), S::Error> {
+        self.sender.send_batch(&self.held.release())?;
+        self.held = HeldKeys::new();
+        Ok(())
+    }
+}
+
+impl<S: KeystrokeSender> KeystrokeSender for ReleaseGuard<'_, S> {
+    type Error = S::Error;
+
+    fn send(&mut self, keystroke: Keystroke, delay: Option<Duration>) -> Result<(), S::Error> {
+        // A failed send may still have delivered a press, but a release only counts once it went out
+        if !matches!(keystroke, Keystroke::Up(_)) {
+            self.held.track(keystroke);
+        }
+        self.sender.send(keystroke, delay)?;
+        self.held.track(keystroke);
+        Ok(())
+    }
+
+    fn send_batch(&mut self, keystrokes: &[Keystroke]) -> Result<(), S::Error> {
+        for &keystroke in keystrokes.iter().filter(|keystroke| !matches!(keystroke, Keystroke::Up(_))) {
+            self.held.track(keystroke);
+        }
+        self.sender.send_batch(keystrokes)?;
+        for &keystroke in keystrokes {
+            self.held.track(keystroke);
+        }
+        Ok(())
+    }
+}
+
+impl<S: KeystrokeSender> Drop for ReleaseGuard<'_, S> {
+    fn drop(&mut self) {
+        // Nothing useful can be done with an error while dropping
+        let _ = self.release();
+    }
+}
+
+
+
+/* ### --- UNIT TEST --- ### */
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sender::RecordingSender;
+
+    #[test]
+    fn test_cleanup_every_prefix() {
+        let keystrokes = crate::to_keystrokes_new("aBC dE!").unwrap();
+
+        for sent in 0..=keystrokes.len() {
+            let mut keyboard = crate::VirtualKeyboard::new(crate::KeyboardLayout::us());
+            keyboard.type_keystrokes(&keystrokes[..sent]);
+            keyboard.press_all(&cleanup_keystrokes(&keystrokes, sent));
+
+            assert!(keyboard.finish().stuck_keys.is_empty(), "prefix {sent}");
+        }
+    }
+
+    #[test]
+    fn test_cleanup_releases_in_reverse() {
+        let sent = crate::parse_notation("⌃↓ ⇧↓ A↓ A↻ ⇧↑ W↓").unwrap();
+
+        assert_eq!(cleanup(&sent), crate::parse_notation("W↑ A↑ ⌃↑").unwrap());
+    }
+
+    #[test]
+    fn test_preamble() {
+        let mut sender = RecordingSender::new();
+        drop(ReleaseGuard::with_preamble(&mut sender).unwrap());
+
+        assert_eq!(sender.keystrokes(), release_all_modifiers());
+        assert!(sender.keystrokes().contains(&Keystroke::Up(VirtualKey::RSHIFT)));
+    }
+
+    #[test]
+    fn test_guard_releases_on_early_return() {
+        fn type_until_error(sender: &mut RecordingSender) -> Result<(), ()> {
+            let mut guard = ReleaseGuard::new(sender);
+            guard.send_keystrokes(&crate::to_keystrokes_new("AB").unwrap()[..2]).unwrap();
+            assert_eq!(guard.held_keys(), [VirtualKey::SHIFT]);
+            Err(())
+        }
+        let mut sender = RecordingSender::new();
+        assert!(type_until_error(&mut sender).is_err());
+
+        assert_eq!(sender.keystrokes().last(), Some(&Keystroke::Up(VirtualKey::SHIFT)));
+    }
+
+    #[test]
+    fn test_guard_releases_on_panic() {
+        let mut sender = RecordingSender::new();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let mut guard = ReleaseGuard::new(&mut sender);
+            guard.send(Keystroke::Down(VirtualKey::CONTROL), None).unwrap();
+            panic!("cancelled");
+        }));
+
+        assert!(result.is_err());
+        assert_eq!(sender.keystrokes(), vec![Keystroke::Down(VirtualKey::CONTROL), Keystroke::Up(VirtualKey::CONTROL)]);
+    }
+
+    /// Fails the first time it is asked to send `fail_once` and counts the batches it gets.
+    #[derive(Default)]
+    struct FlakySender {
+        fail_once: Option<Keystroke>,
+        batches: usize,
+        sent: Vec<Keystroke>,
+    }
+
+    impl KeystrokeSender for FlakySender {
+        type Error = ();
+
+        fn send(&mut self, keystroke: Keystroke, _delay: Option<Duration>) -> Result<(), ()> {
+            if self.fail_once == Some(keystroke) {
+                self.fail_once = None;
+                return Err(())
+            }
+            self.sent.push(keystroke);
+            Ok(())
+        }
+
+        fn send_batch(&mut self, keystrokes: &[Keystroke]) -> Result<(), ()> {
+            self.batches += 1;
+            keystrokes.iter().try_for_each(|&keystroke| self.send(keystroke, None))
+        }
+    }
+
+    #[test]
+    fn test_failed_release_is_retried() {
+        let shift = VirtualKey::SHIFT;
+        let mut sender = FlakySender { fail_once: Some(Keystroke::Up(shift)), ..FlakySender::default() };
+        let mut guard = ReleaseGuard::new(&mut sender);
+        guard.send(Keystroke::Down(shift), None).unwrap();
+
+        assert_eq!(guard.send(Keystroke::Up(shift), None), Err(()));
+        assert_eq!(guard.held_keys(), [shift]);
+        drop(guard);
+        assert_eq!(sender.sent, [Keystroke::Down(shift), Keystroke::Up(shift)]);
+    }
+
+    #[test]
+    fn test_failed_release_call_is_retried_on_drop() {
+        let control = VirtualKey::CONTROL;
+        let mut sender = FlakySender { fail_once: Some(Keystroke::Up(control)), ..FlakySender::default() };
+        let mut guard = ReleaseGuard::new(&mut sender);
+        guard.send(Keystroke::Down(control), None).unwrap();
+
+        assert_eq!(guard.release(), Err(()));
+        assert_eq!(guard.held_keys(), [control]);
+        drop(guard);
+        assert_eq!(sender.sent, [Keystroke::Down(control), Keystroke::Up(control)]);
+    }
+
+    #[test]
+    fn test_batches_are_forwarded() {
+        let mut sender = FlakySender::default();
+        let mut guard = ReleaseGuard::new(&mut sender);
+        guard.send_batch(&crate::parse_notation("⇧↓ A ⇧↑ ⌃↓").unwrap()).unwrap();
+
+        assert_eq!(guard.held_keys(), [VirtualKey::CONTROL]);
+        drop(guard);
+        assert_eq!(sender.batches, 2);
+        assert_eq!(sender.sent.last(), Some(&Keystroke::Up(VirtualKey::CONTROL)));
+    }
+
+    #[test]
+    fn test_nothing_to_release() {
+        let mut sender = RecordingSender::new();
+        ReleaseGuard::new(&mut sender).send_keystrokes(&crate::to_keystrokes_new("Hi").unwrap()).unwrap();
+
+        assert_eq!(sender.keystrokes(), crate::decode_keystrokes(&crate::to_keystrokes_new("Hi").unwrap()));
+    }
+}
@@ -0,0 +1,324 @@
+use crate::virtual_key::VirtualKey;
+
+// See: https://learn.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-mapvirtualkeyw
+// Scan codes are from scan code set 1, as reported by Windows for US 101/102-key keyboards.
+
+/// Prefix Windows places in the high byte of extended scan codes, e.g. `0xE01D` for right CTRL.
+pub const EXTENDED_PREFIX: u16 = 0xE000;
+/// Prefix used only by the pause key, `0xE11D`.
+pub const PAUSE_PREFIX: u16 = 0xE100;
+
+/// The translation performed by [`map_virtual_key`]. Values match the win32 `MAPVK_*` constants.
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MapType {
+    /// `MAPVK_VK_TO_VSC`: virtual key to scan code, without any prefix. Left and right modifiers are
+    /// not distinguished, generic modifiers give the left hand scan code.
+    VkToVsc = 0,
+    /// `MAPVK_VSC_TO_VK`: scan code to virtual key. Left and right modifiers collapse into the
+    /// generic `VK_SHIFT`, `VK_CONTROL` and `VK_MENU`.
+    VscToVk = 1,
+    /// `MAPVK_VSC_TO_VK_EX`: scan code to virtual key, keeping left and right modifiers apart.
+    VscToVkEx = 3,
+    /// `MAPVK_VK_TO_VSC_EX`: virtual key to scan code, with the `0xE0`/`0xE1` prefix in the high byte
+    /// for extended keys.
+    VkToVscEx = 4,
+}
+
+/// Every key on a US 101/102-key keyboard. When a scan code or virtual key appears more than once the
+/// first entry is the one reported, so the order below is significant.
+static SCAN_CODES: &[(u16, VirtualKey)] = &[
+    // Main block
+    (0x01, VirtualKey::ESCAPE),
+    (0x02, VirtualKey::KEY_1),
+    (0x03, VirtualKey::KEY_2),
+    (0x04, VirtualKey::KEY_3),
+    (0x05, VirtualKey::KEY_4),
+    (0x06, VirtualKey::KEY_5),
+    (0x07, VirtualKey::KEY_6),
+    (0x08, VirtualKey::KEY_7),
+    (0x09, VirtualKey::KEY_8),
+    (0x0A, VirtualKey::KEY_9),
+    (0x0B, VirtualKey::KEY_0),
+    (0x0C, VirtualKey::OEM_MINUS),
+    (0x0D, VirtualKey::OEM_PLUS),
+    (0x0E, VirtualKey::BACK),
+    (0x0F, VirtualKey::TAB),
+    (0x10, VirtualKey::KEY_Q),
+    (0x11, VirtualKey::KEY_W),
+    (0x12, VirtualKey::KEY_E),
+    (0x13, VirtualKey::KEY_R),
+    (0x14, VirtualKey::KEY_T),
+    (0x15, VirtualKey::KEY_Y),
+    (0x16, VirtualKey::KEY_U),
+    (0x17, VirtualKey::KEY_I),
+    (0x18, VirtualKey::KEY_O),
+    (0x19, VirtualKey::KEY_P),
+    (0x1A, VirtualKey::OEM_4),
+    (0x1B, VirtualKey::OEM_6),
+    (0x1C, VirtualKey::RETURN),
+    (0x1D, VirtualKey::LCONTROL),
+    (0x1E, VirtualKey::KEY_A),
+    (0x1F, VirtualKey::KEY_S),
+    (0x20, VirtualKey::KEY_D),
+    (0x21, VirtualKey::KEY_F),
+    (0x22, VirtualKey::KEY_G),
+    (0x23, VirtualKey::KEY_H),
+    (0x24, VirtualKey::KEY_J),
+    (0x25, VirtualKey::KEY_K),
+    (0x26, VirtualKey::KEY_L),
+    (0x27, VirtualKey::OEM_1),
+    (0x28, VirtualKey::OEM_7),
+    (0x29, VirtualKey::OEM_3),
+    (0x2A, VirtualKey::LSHIFT),
+    (0x2B, VirtualKey::OEM_5),
+    (0x2C, VirtualKey::KEY_Z),
+    (0x2D, VirtualKey::KEY_X),
+    (0x2E, VirtualKey::KEY_C),
+    (0x2F, VirtualKey::KEY_V),
+    (0x30, VirtualKey::KEY_B),
+    (0x31, VirtualKey::KEY_N),
+    (0x32, VirtualKey::KEY_M),
+    (0x33, VirtualKey::OEM_COMMA),
+    (0x34, VirtualKey::OEM_PERIOD),
+    (0x35, VirtualKey::OEM_2),
+    (0x36, VirtualKey::RSHIFT),
+    (0x37, VirtualKey::MULTIPLY),
+    (0x38, VirtualKey::LMENU),
+    (0x39, VirtualKey::SPACE),
+    (0x3A, VirtualKey::CAPITAL),
+    (0x3B, VirtualKey::F1),
+    (0x3C, VirtualKey::F2),
+    (0x3D, VirtualKey::F3),
+    (0x3E, VirtualKey::F4),
+    (0x3F, VirtualKey::F5),
+    (0x40, VirtualKey::F6),
+    (0x41, VirtualKey::F7),
+    (0x42, VirtualKey::F8),
+    (0x43, VirtualKey::F9),
+    (0x44, VirtualKey::F10),
+    (0x46, VirtualKey::SCROLL),
+    (0x4A, VirtualKey::SUBTRACT),
+    (0x4E, VirtualKey::ADD),
+    (0x54, VirtualKey::SNAPSHOT), // Alt + Print Screen (SysRq)
+    (0x56, VirtualKey::OEM_102), // 102-key only, between left shift and Z
+    (0x57, VirtualKey::F11),
+    (0x58, VirtualKey::F12),
+
+    // Generic modifiers, only reachable from a virtual key as the left/right entries above come first
+    (0x2A, VirtualKey::SHIFT),
+    (0x1D, VirtualKey::CONTROL),
+    (0x38, VirtualKey::MENU),
+
+    // Extended keys (0xE0 prefix)
+    (0xE01C, VirtualKey::RETURN), // Numpad enter
+    (0xE01D, VirtualKey::RCONTROL),
+    (0xE035, VirtualKey::DIVIDE),
+    (0xE037, VirtualKey::SNAPSHOT),
+    (0xE038, VirtualKey::RMENU),
+    (0xE045, VirtualKey::NUMLOCK),
+    (0xE046, VirtualKey::CANCEL), // Ctrl + Break
+    (0xE047, VirtualKey::HOME),
+    (0xE048, VirtualKey::UP),
+    (0xE049, VirtualKey::PRIOR),
+    (0xE04B, VirtualKey::LEFT),
+    (0xE04D, VirtualKey::RIGHT),
+    (0xE04F, VirtualKey::END),
+    (0xE050, VirtualKey::DOWN),
+    (0xE051, VirtualKey::NEXT),
+    (0xE052, VirtualKey::INSERT),
+    (0xE053, VirtualKey::DELETE),
+    (0xE05B, VirtualKey::LWIN),
+    (0xE05C, VirtualKey::RWIN),
+    (0xE05D, VirtualKey::APPS),
+
+    // Pause (0xE1 prefix)
+    (0xE11D, VirtualKey::PAUSE),
+
+    // Numpad without num lock, these share the navigation virtual keys above
+    (0x45, VirtualKey::NUMLOCK),
+    (0x47, VirtualKey::HOME),
+    (0x48, VirtualKey::UP),
+    (0x49, VirtualKey::PRIOR),
+    (0x4B, VirtualKey::LEFT),
+    (0x4C, VirtualKey::CLEAR),
+    (0x4D, VirtualKey::RIGHT),
+    (0x4F, VirtualKey::END),
+    (0x50, VirtualKey::DOWN),
+    (0x51, VirtualKey::NEXT),
+    (0x52, VirtualKey::INSERT),
+    (0x53, VirtualKey::DELETE),
+
+    // Numpad with num lock, only reachable from a virtual key
+    (0x47, VirtualKey::NUMPAD7),
+    (0x48, VirtualKey::NUMPAD8),
+    (0x49, VirtualKey::NUMPAD9),
+    (0x4B, VirtualKey::NUMPAD4),
+    (0x4C, VirtualKey::NUMPAD5),
+    (0x4D, VirtualKey::NUMPAD6),
+    (0x4F, VirtualKey::NUMPAD1),
+    (0x50, VirtualKey::NUMPAD2),
+    (0x51, VirtualKey::NUMPAD3),
+    (0x52, VirtualKey::NUMPAD0),
+    (0x53, VirtualKey::DECIMAL),
+];
+
+/// Translates between virtual keys and scan codes like win32's `MapVirtualKeyW`, using the tables for a
+/// US 101/102-key keyboard instead of the active layout.
+///
+/// As with win32, `0` is returned when there is no translation.
+pub fn map_virtual_key(code: u32, map_type: MapType) -> u32 {
+    match map_type {
+        MapType::VkToVsc => vk_to_scan_code(code)
+            .map_or(0, |scan| (scan & 0xFF) as u32),
+        MapType::VkToVscEx => vk_to_scan_code(code)
+            .map_or(0, u32::from),
+        MapType::VscToVk => scan_code_to_vk(code)
+            .map_or(0, |vk| u32::from(generic_modifier(vk).0)),
+        MapType::VscToVkEx => scan_code_to_vk(code)
+            .map_or(0, |vk| u32::from(vk.0)),
+    }
+}
+
+/// The scan code for `vk`, including the `0xE0`/`0xE1` prefix for extended keys.
+pub fn scan_code(vk: VirtualKey) -> Option<u16> {
+    SCAN_CODES.iter()
+        .find(|&&(_, known)| known == vk)
+        .map(|&(scan, _)| scan)
+}
+
+/// Whether `scan` carries the `0xE0` extended key prefix, meaning `KEYEVENTF_EXTENDEDKEY` must be set
+/// when sending it.
+pub fn is_extended(scan: u16) -> bool {
+    scan & 0xFF00 == EXTENDED_PREFIX
+}
+
+fn vk_to_scan_code(code: u32) -> Option<u16> {
+    u8::try_from(code).ok().and_then(|vk| scan_code(VirtualKey(vk)))
+}
+
+fn scan_code_to_vk(code: u32) -> Option<VirtualKey> {
+    virtual_key(u16::try_from(code).ok()?)
+}
+
+/// The virtual key for `scan` on a US keyboard, the inverse of [`scan_code`].
+pub(crate) fn virtual_key(scan: u16) -> Option<VirtualKey> {
+    SCAN_CODES.iter()
+        .find(|&&(known, _)| known == scan)
+        .map(|&(_, vk)| vk)
+}
+
+/// Collapses left and right modifiers into their generic virtual key.
+fn generic_modifier(vk: VirtualKey) -> VirtualKey {
+    match vk {
+        VirtualKey::LSHIFT | VirtualKey::RSHIFT => VirtualKey::SHIFT,
+        VirtualKey::LCONTROL | VirtualKey::RCONTROL => VirtualKey::CONTROL,
+        VirtualKey::LMENU | VirtualKey::RMENU => VirtualKey::MENU,
+        _ => vk,
+    }
+}
+
+
+
+/* ### --- UNIT TEST --- ### */
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vk(vk: VirtualKey) -> u32 {
+        u32::from(vk.0)
+    }
+
+    #[test]
+    fn test_vk_to_vsc() {
+        assert_eq!(map_virtual_key(vk(VirtualKey::KEY_A), MapType::VkToVsc), 0x1E);
+        assert_eq!(map_virtual_key(vk(VirtualKey::ESCAPE), MapType::VkToVsc), 0x01);
+        assert_eq!(map_virtual_key(vk(VirtualKey::OEM_102), MapType::VkToVsc), 0x56);
+    }
+
+    #[test]
+    fn test_vk_to_vsc_generic_modifiers_use_left_hand() {
+        assert_eq!(map_virtual_key(vk(VirtualKey::SHIFT), MapType::VkToVsc), 0x2A);
+        assert_eq!(map_virtual_key(vk(VirtualKey::CONTROL), MapType::VkToVsc), 0x1D);
+        assert_eq!(map_virtual_key(vk(VirtualKey::MENU), MapType::VkToVsc), 0x38);
+        assert_eq!(map_virtual_key(vk(VirtualKey::RSHIFT), MapType::VkToVsc), 0x36);
+    }
+
+    #[test]
+    fn test_vk_to_vsc_drops_prefix() {
+        assert_eq!(map_virtual_key(vk(VirtualKey::RCONTROL), MapType::VkToVsc), 0x1D);
+        assert_eq!(map_virtual_key(vk(VirtualKey::LEFT), MapType::VkToVsc), 0x4B);
+    }
+
+    #[test]
+    fn test_vk_to_vsc_ex_keeps_prefix() {
+        assert_eq!(map_virtual_key(vk(VirtualKey::RCONTROL), MapType::VkToVscEx), 0xE01D);
+        assert_eq!(map_virtual_key(vk(VirtualKey::RMENU), MapType::VkToVscEx), 0xE038);
+        assert_eq!(map_virtual_key(vk(VirtualKey::LEFT), MapType::VkToVscEx), 0xE04B);
+        assert_eq!(map_virtual_key(vk(VirtualKey::DIVIDE), MapType::VkToVscEx), 0xE035);
+        assert_eq!(map_virtual_key(vk(VirtualKey::PAUSE), MapType::VkToVscEx), 0xE11D);
+        assert_eq!(map_virtual_key(vk(VirtualKey::NUMPAD4), MapType::VkToVscEx), 0x4B);
+        assert_eq!(map_virtual_key(vk(VirtualKey::LCONTROL), MapType::VkToVscEx), 0x1D);
+    }
+
+    #[test]
+    fn test_vsc_to_vk_collapses_modifiers() {
+        assert_eq!(map_virtual_key(0x2A, MapType::VscToVk), vk(VirtualKey::SHIFT));
+        assert_eq!(map_virtual_key(0x36, MapType::VscToVk), vk(VirtualKey::SHIFT));
+        assert_eq!(map_virtual_key(0xE01D, MapType::VscToVk), vk(VirtualKey::CONTROL));
+        assert_eq!(map_virtual_key(0xE038, MapType::VscToVk), vk(VirtualKey::MENU));
+    }
+
+    #[test]
+    fn test_vsc_to_vk_ex_keeps_left_and_right() {
+        assert_eq!(map_virtual_key(0x2A, MapType::VscToVkEx), vk(VirtualKey::LSHIFT));
+        assert_eq!(map_virtual_key(0x36, MapType::VscToVkEx), vk(VirtualKey::RSHIFT));
+        assert_eq!(map_virtual_key(0x1D, MapType::VscToVkEx), vk(VirtualKey::LCONTROL));
+        assert_eq!(map_virtual_key(0xE01D, MapType::VscToVkEx), vk(VirtualKey::RCONTROL));
+        assert_eq!(map_virtual_key(0x38, MapType::VscToVkEx), vk(VirtualKey::LMENU));
+        assert_eq!(map_virtual_key(0xE038, MapType::VscToVkEx), vk(VirtualKey::RMENU));
+    }
+
+    #[test]
+    fn test_vsc_to_vk_numpad_and_navigation() {
+        assert_eq!(map_virtual_key(0x4B, MapType::VscToVk), vk(VirtualKey::LEFT));
+        assert_eq!(map_virtual_key(0xE04B, MapType::VscToVk), vk(VirtualKey::LEFT));
+        assert_eq!(map_virtual_key(0xE01C, MapType::VscToVkEx), vk(VirtualKey::RETURN));
+        assert_eq!(map_virtual_key(0xE11D, MapType::VscToVkEx), vk(VirtualKey::PAUSE));
+    }
+
+    #[test]
+    fn test_no_translation_is_zero() {
+        assert_eq!(map_virtual_key(vk(VirtualKey::F24), MapType::VkToVsc), 0);
+        assert_eq!(map_virtual_key(0x7F, MapType::VscToVk), 0);
+        assert_eq!(map_virtual_key(0x1_0000, MapType::VscToVkEx), 0);
+        assert_eq!(map_virtual_key(0x100, MapType::VkToVscEx), 0);
+    }
+
+    #[test]
+    fn test_is_extended() {
+        assert!(is_extended(0xE01D));
+        assert!(!is_extended(0xE11D));
+        assert!(!is_extended(0x1D));
+    }
+
+    #[test]
+    fn test_translation_output_has_scan_codes() {
+        let strokes = crate::to_keystrokes_new("Hello, World! ~`*-/").unwrap();
+        for stroke in strokes {
+            assert!(scan_code(VirtualKey(stroke)).is_some(), "no scan code for {stroke:#04x}");
+        }
+    }
+
+    #[test]
+    fn test_scan_codes_round_trip() {
+        for &(scan, vk) in SCAN_CODES {
+            let found = map_virtual_key(u32::from(scan), MapType::VscToVkEx);
+            let back = map_virtual_key(found, MapType::VkToVscEx);
+            assert_eq!(map_virtual_key(back, MapType::VscToVkEx), found, "{vk:?} did not round trip");
+        }
+    }
+}
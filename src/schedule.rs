@@ -0,0 +1,228 @@
+use std::time::Duration;
+
+use crate::keystroke::{decode_keystrokes, is_modifier, Keystroke};
+use crate::layout::{to_unicode, KeyboardLayout, ShiftState};
+use crate::virtual_key::VirtualKey;
+
+/// Pacing rules used to turn key events into timed events.
+///
+/// Every produced event carries the time to wait after sending it. The schedule is computed from the
+/// events alone, so the same input always gives the same output.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TypingScheduler {
+    /// Target typing speed. Each typed character takes `1 / chars_per_second` seconds including its
+    /// hold time. Zero or a non-finite value removes the per-character time.
+    pub chars_per_second: f64,
+    /// Wait after pressing or releasing a modifier.
+    pub modifier_gap: Duration,
+    /// How long a tapped key is held down. When zero, taps are kept as a single [`Keystroke::Tap`],
+    /// otherwise they are split into a [`Keystroke::Down`] and [`Keystroke::Up`].
+    pub key_hold: Duration,
+    /// Extra wait after typing a new line.
+    pub newline_pause: Duration,
+    /// Extra wait after typing one of the characters in [`TypingScheduler::punctuation`].
+    pub punctuation_pause: Duration,
+    /// Characters followed by [`TypingScheduler::punctuation_pause`].
+    pub punctuation: String,
+    /// No wait is ever shorter than this.
+    pub min_gap: Duration,
+}
+
+impl Default for TypingScheduler {
+    fn default() -> Self {
+        TypingScheduler {
+            chars_per_second: 20.0,
+            modifier_gap: Duration::from_millis(10),
+            key_hold: Duration::ZERO,
+            newline_pause: Duration::ZERO,
+            punctuation_pause: Duration::ZERO,
+            punctuation: String::from(".,;:!?"),
+            min_gap: Duration::from_millis(1),
+        }
+    }
+}
+
+impl TypingScheduler {
+    /// The default pacing rules at the given typing speed.
+    pub fn at_speed(chars_per_second: f64) -> TypingScheduler {
+        TypingScheduler { chars_per_second, ..TypingScheduler::default() }
+    }
+
+    /// The time each typed character takes, hold time included. Speeds so slow the interval does not
+    /// fit a [`Duration`] give [`Duration::MAX`].
+    pub fn char_interval(&self) -> Duration {
+        if self.chars_per_second > 0.0 && self.chars_per_second.is_finite() {
+            Duration::try_from_secs_f64(1.0 / self.chars_per_second).unwrap_or(Duration::MAX)
+        } else {
+            Duration::ZERO
+        }
+    }
+
+    /// Assigns a delay to every event. `layout` is used to recognise the characters that earn a
+    /// pause.
+    pub fn schedule(&self, keystrokes: &[Keystroke], layout: &KeyboardLayout) -> Vec<(Keystroke, Duration)> {
+        let mut timed = Vec::with_capacity(keystrokes.len());
+        let mut shift_state = ShiftState::NONE;
+
+        for &keystroke in keystrokes {
+            let vk = keystroke.vk();
+            if is_modifier(vk) {
+                match keystroke {
+                    Keystroke::Down(_) => shift_state |= ShiftState::for_modifier(vk),
+                    Keystroke::Up(_) => shift_state = shift_state.difference(ShiftState::for_modifier(vk)),
+                    Keystroke::Tap(_) | Keystroke::Repeat(_) => {}
+                }
+                timed.push((keystroke, self.gap(self.modifier_gap)));
+                continue
+            }
+
+            let pause = self.pause_after(vk, shift_state, layout);
+            let after_release = self.gap(self.char_interval().saturating_sub(self.key_hold).saturating_add(pause));
+            match keystroke {
+                Keystroke::Tap(_) if !self.key_hold.is_zero() => {
+                    timed.push((Keystroke::Down(vk), self.gap(self.key_hold)));
+                    timed.push((Keystroke::Up(vk), after_release));
+                }
+                Keystroke::Down(_) | Keystroke::Repeat(_) => timed.push((keystroke, self.gap(self.key_hold))),
+                Keystroke::Up(_) | Keystroke::Tap(_) => timed.push((keystroke, after_release)),
+            }
+        }
+
+        timed
+    }
+
+    /// Assigns a delay to every event in the flat output of [`crate::to_keystrokes_new`].
+    pub fn schedule_keystrokes(&self, keystrokes: &[u8], layout: &KeyboardLayout) -> Vec<(Keystroke, Duration)> {
+        self.schedule(&decode_keystrokes(keystrokes), layout)
+    }
+
+    fn pause_after(&self, vk: VirtualKey, shift_state: ShiftState, layout: &KeyboardLayout) -> Duration {
+        if vk == VirtualKey::RETURN {
+            return self.newline_pause
+        }
+        match to_unicode(vk, shift_state, layout) {
+            Some('\n') => self.newline_pause,
+            Some(character) if self.punctuation.contains(character) => self.punctuation_pause,
+            _ => Duration::ZERO,
+        }
+    }
+
+    fn gap(&self, gap: Duration) -> Duration {
+        gap.max(self.min_gap)
+    }
+}
+
+/// The total time a schedule takes to send, [`Duration::MAX`] when it is longer than that.
+pub fn total_duration(timed: &[(Keystroke, Duration)]) -> Duration {
+    timed.iter().fold(Duration::ZERO, |total, &(_, delay)| total.saturating_add(delay))
+}
+
+
+
+/* ### --- UNIT TEST --- ### */
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_constant_speed() {
+        let scheduler = TypingScheduler::at_speed(10.0);
+        let timed = scheduler.schedule_keystrokes(&crate::to_keystrokes_new("abc").unwrap(), KeyboardLayout::us());
+
+        assert_eq!(timed, vec![
+            (Keystroke::Tap(VirtualKey::KEY_A), ms(100)),
+            (Keystroke::Tap(VirtualKey::KEY_B), ms(100)),
+            (Keystroke::Tap(VirtualKey::KEY_C), ms(100)),
+        ]);
+    }
+
+    #[test]
+    fn test_modifier_gap() {
+        let scheduler = TypingScheduler { modifier_gap: ms(7), ..TypingScheduler::at_speed(10.0) };
+        let timed = scheduler.schedule_keystrokes(&crate::to_keystrokes_new("A").unwrap(), KeyboardLayout::us());
+
+        assert_eq!(timed, vec![
+            (Keystroke::Down(VirtualKey::SHIFT), ms(7)),
+            (Keystroke::Tap(VirtualKey::KEY_A), ms(100)),
+            (Keystroke::Up(VirtualKey::SHIFT), ms(7)),
+        ]);
+    }
+
+    #[test]
+    fn test_key_hold_splits_taps() {
+        let scheduler = TypingScheduler { key_hold: ms(30), ..TypingScheduler::at_speed(10.0) };
+        let timed = scheduler.schedule_keystrokes(&crate::to_keystrokes_new("a").unwrap(), KeyboardLayout::us());
+
+        assert_eq!(timed, vec![
+            (Keystroke::Down(VirtualKey::KEY_A), ms(30)),
+            (Keystroke::Up(VirtualKey::KEY_A), ms(70)),
+        ]);
+    }
+
+    #[test]
+    fn test_punctuation_pause_respects_shift() {
+        let scheduler = TypingScheduler { punctuation_pause: ms(200), ..TypingScheduler::at_speed(10.0) };
+        // '1' and '!' share a key, only the shifted one is punctuation
+        let timed = scheduler.schedule_keystrokes(&crate::to_keystrokes_new("1!").unwrap(), KeyboardLayout::us());
+
+        assert_eq!(timed[0], (Keystroke::Tap(VirtualKey::KEY_1), ms(100)));
+        assert_eq!(timed[2], (Keystroke::Tap(VirtualKey::KEY_1), ms(300)));
+    }
+
+    #[test]
+    fn test_newline_pause() {
+        let scheduler = TypingScheduler { newline_pause: ms(500), ..TypingScheduler::at_speed(10.0) };
+        let timed = scheduler.schedule(&[Keystroke::Tap(VirtualKey::RETURN)], KeyboardLayout::us());
+
+        assert_eq!(timed, vec![(Keystroke::Tap(VirtualKey::RETURN), ms(600))]);
+    }
+
+    #[test]
+    fn test_min_gap() {
+        let scheduler = TypingScheduler { min_gap: ms(5), modifier_gap: ms(0), ..TypingScheduler::at_speed(0.0) };
+        let timed = scheduler.schedule_keystrokes(&crate::to_keystrokes_new("Ab").unwrap(), KeyboardLayout::us());
+
+        assert!(timed.iter().all(|&(_, delay)| delay == ms(5)));
+    }
+
+    #[test]
+    fn test_tiny_speed_is_clamped() {
+        let scheduler = TypingScheduler { punctuation_pause: ms(200), ..TypingScheduler::at_speed(1e-30) };
+        let timed = scheduler.schedule_keystrokes(&crate::to_keystrokes_new("a.").unwrap(), KeyboardLayout::us());
+
+        assert_eq!(scheduler.char_interval(), Duration::MAX);
+        assert!(timed.iter().all(|&(_, delay)| delay == Duration::MAX));
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let scheduler = TypingScheduler { key_hold: ms(12), punctuation_pause: ms(80), ..TypingScheduler::default() };
+        let strokes = crate::to_keystrokes_new("Hello, World!").unwrap();
+
+        assert_eq!(scheduler.schedule_keystrokes(&strokes, KeyboardLayout::us()),
+            scheduler.schedule_keystrokes(&strokes, KeyboardLayout::us()));
+    }
+
+    #[test]
+    fn test_total_duration() {
+        let timed = TypingScheduler::at_speed(10.0)
+            .schedule_keystrokes(&crate::to_keystrokes_new("abcd").unwrap(), KeyboardLayout::us());
+
+        assert_eq!(total_duration(&timed), ms(400));
+    }
+
+    #[test]
+    fn test_total_duration_saturates() {
+        let timed = TypingScheduler::at_speed(1e-30)
+            .schedule_keystrokes(&crate::to_keystrokes_new("abcd").unwrap(), KeyboardLayout::us());
+
+        assert_eq!(total_duration(&timed), Duration::MAX);
+    }
+}
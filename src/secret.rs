@@ -0,0 +1,90 @@
+use std::fmt;
+use std::ops::Deref;
+
+use zeroize::Zeroizing;
+
+use crate::KeyError;
+
+/// Keystrokes for secret text such as a password or one-time code. The buffer is wiped when dropped
+/// and its contents are never printed by [`fmt::Debug`].
+#[derive(Clone, PartialEq, Eq)]
+pub struct SecretKeystrokes(Zeroizing<Vec<u8>>);
+
+impl SecretKeystrokes {
+    /// The keystrokes, in the same format as [`crate::to_keystrokes_new`].
+    pub fn expose(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Deref for SecretKeystrokes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for SecretKeystrokes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretKeystrokes([REDACTED])")
+    }
+}
+
+/// Translates secret text like [`crate::to_keystrokes_new`] without leaving copies behind.
+///
+/// Accepts anything that borrows as a `str`, including `zeroize::Zeroizing<String>`. The buffer is
+/// allocated once at its largest possible size so growing it never leaves a stale copy in freed
+/// memory, and it is wiped on drop, including when translation fails partway. Errors are redacted:
+/// [`KeyError::byte`] is always zero so the character that failed is never revealed.
+pub fn to_keystrokes_secret<S: AsRef<str> + ?Sized>(keys: &S) -> Result<SecretKeystrokes, KeyError> {
+    let keys = keys.as_ref();
+    // At most one shift toggle per character plus its key, and a final shift release
+    let mut keystrokes = Zeroizing::new(Vec::with_capacity(keys.len() * 2 + 1));
+
+    match crate::to_keystrokes_mut(keys, &mut keystrokes) {
+        Ok(()) => Ok(SecretKeystrokes(keystrokes)),
+        Err(err) => Err(KeyError { byte: 0, error_code: err.error_code }),
+    }
+}
+
+
+
+/* ### --- UNIT TEST --- ### */
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorCodes;
+
+    #[test]
+    fn test_matches_translator() {
+        let password = Zeroizing::new(String::from("Hunter2!{Pass}"));
+        let keystrokes = to_keystrokes_secret(&password).unwrap();
+
+        assert_eq!(keystrokes.expose(), crate::to_keystrokes_new(&password).unwrap());
+    }
+
+    #[test]
+    fn test_buffer_never_grows() {
+        // Every character toggles shift, the worst case
+        let text = "aBcDeFgHiJ";
+        let keystrokes = to_keystrokes_secret(text).unwrap();
+
+        assert_eq!(keystrokes.len(), text.len() * 2);
+        assert_eq!(keystrokes.0.capacity(), text.len() * 2 + 1);
+    }
+
+    #[test]
+    fn test_error_is_redacted() {
+        assert_eq!(to_keystrokes_secret("pässword").unwrap_err(), KeyError { byte: 0, error_code: ErrorCodes::OutOfRange });
+        assert_eq!(to_keystrokes_secret("pass\u{7F}").unwrap_err(), KeyError { byte: 0, error_code: ErrorCodes::OutOfRange });
+    }
+
+    #[test]
+    fn test_debug_is_redacted() {
+        assert_eq!(format!("{:?}", to_keystrokes_secret("secret").unwrap()), "SecretKeystrokes([REDACTED])");
+    }
+}
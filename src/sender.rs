@@ -0,0 +1,260 @@
+use std::convert::Infallible;
+use std::time::{Duration, Instant};
+
+use crate::keystroke::{decode_keystrokes, Keystroke};
+use crate::recording::TimedKeystroke;
+use crate::simulator::VirtualKeyboard;
+
+/// Something that delivers key events, such as a `SendInput` wrapper on Windows.
+///
+/// Code that depends on this trait rather than calling win32 directly can be tested anywhere with a
+/// [`RecordingSender`] or a [`VirtualKeyboard`].
+pub trait KeystrokeSender {
+    type Error;
+
+    /// Sends one event, then waits for `delay` when one is given.
+    fn send(&mut self, keystroke: Keystroke, delay: Option<Duration>) -> Result<(), Self::Error>;
+
+    /// Sends events in order, stopping at the first error.
+    fn send_all(&mut self, keystrokes: &[TimedKeystroke]) -> Result<(), Self::Error> {
+        for &(keystroke, delay) in keystrokes {
+            self.send(keystroke, delay)?;
+        }
+        Ok(())
+    }
+
+    /// Sends several events without delays between them. Backends that can deliver many events at
+    /// once, such as one `SendInput` call with an array of inputs, should override this.
+    fn send_batch(&mut self, keystrokes: &[Keystroke]) -> Result<(), Self::Error> {
+        for &keystroke in keystrokes {
+            self.send(keystroke, None)?;
+        }
+        Ok(())
+    }
+
+    /// Sends the output of [`crate::TypingScheduler::schedule`] or [`crate::RhythmModel::schedule`].
+    fn send_schedule(&mut self, timed: &[(Keystroke, Duration)]) -> Result<(), Self::Error> {
+        for &(keystroke, delay) in timed {
+            self.send(keystroke, Some(delay))?;
+        }
+        Ok(())
+    }
+
+    /// Sends the flat output of [`crate::to_keystrokes_new`] without delays.
+    fn send_keystrokes(&mut self, keystrokes: &[u8]) -> Result<(), Self::Error> {
+        for keystroke in decode_keystrokes(keystrokes) {
+            self.send(keystroke, None)?;
+        }
+        Ok(())
+    }
+}
+
+/// A source of time for a [`RecordingSender`].
+pub trait Clock {
+    /// Time elapsed since the clock started.
+    fn now(&self) -> Duration;
+    fn sleep(&mut self, duration: Duration);
+}
+
+/// A clock that only moves when slept on, so tests run instantly and timestamps are exact.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct ManualClock {
+    now: Duration,
+}
+
+impl ManualClock {
+    pub fn new() -> ManualClock {
+        ManualClock::default()
+    }
+
+    /// Moves the clock forward, as if something else took `duration`. The clock stops at
+    /// [`Duration::MAX`].
+    pub fn advance(&mut self, duration: Duration) {
+        self.now = self.now.saturating_add(duration);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        self.now
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+/// The wall clock. Sleeping blocks the current thread.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        SystemClock { start: Instant::now() }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// A [`KeystrokeSender`] that keeps every event in memory along with the time it was sent.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RecordingSender<C: Clock = ManualClock> {
+    clock: C,
+    events: Vec<(Duration, Keystroke)>,
+}
+
+impl RecordingSender<ManualClock> {
+    /// A sender whose clock starts at zero and only advances by the requested delays.
+    pub fn new() -> RecordingSender<ManualClock> {
+        RecordingSender::with_clock(ManualClock::new())
+    }
+}
+
+impl Default for RecordingSender<ManualClock> {
+    fn default() -> Self {
+        RecordingSender::new()
+    }
+}
+
+impl<C: Clock> RecordingSender<C> {
+    pub fn with_clock(clock: C) -> RecordingSender<C> {
+        RecordingSender { clock, events: Vec::new() }
+    }
+
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    pub fn clock_mut(&mut self) -> &mut C {
+        &mut self.clock
+    }
+
+    /// Every event sent so far along with the clock time it was sent at.
+    pub fn events(&self) -> &[(Duration, Keystroke)] {
+        &self.events
+    }
+
+    /// Every event sent so far, without timestamps.
+    pub fn keystrokes(&self) -> Vec<Keystroke> {
+        self.events.iter().map(|&(_, keystroke)| keystroke).collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    pub fn into_events(self) -> Vec<(Duration, Keystroke)> {
+        self.events
+    }
+}
+
+impl<C: Clock> KeystrokeSender for RecordingSender<C> {
+    type Error = Infallible;
+
+    fn send(&mut self, keystroke: Keystroke, delay: Option<Duration>) -> Result<(), Infallible> {
+        self.events.push((self.clock.now(), keystroke));
+        if let Some(delay) = delay {
+            self.clock.sleep(delay);
+        }
+        Ok(())
+    }
+}
+
+/// Presses keys on the simulated keyboard, ignoring delays.
+impl KeystrokeSender for VirtualKeyboard<'_> {
+    type Error = Infallible;
+
+    fn send(&mut self, keystroke: Keystroke, _delay: Option<Duration>) -> Result<(), Infallible> {
+        self.press(keystroke);
+        Ok(())
+    }
+}
+
+
+
+/* ### --- UNIT TEST --- ### */
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::virtual_key::VirtualKey;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    /// Generic code under test only knows about the trait.
+    fn type_greeting<S: KeystrokeSender>(sender: &mut S) -> Result<(), S::Error> {
+        sender.send_keystrokes(&crate::to_keystrokes_new("Hi").unwrap())
+    }
+
+    #[test]
+    fn test_records_events() {
+        let mut sender = RecordingSender::new();
+        type_greeting(&mut sender).unwrap();
+
+        assert_eq!(sender.keystrokes(), crate::decode_keystrokes(&crate::to_keystrokes_new("Hi").unwrap()));
+        assert!(sender.events().iter().all(|&(at, _)| at == Duration::ZERO));
+    }
+
+    #[test]
+    fn test_timestamps_follow_delays() {
+        let mut sender = RecordingSender::new();
+        sender.send_all(&[
+            (Keystroke::Down(VirtualKey::SHIFT), Some(ms(5))),
+            (Keystroke::Tap(VirtualKey::KEY_A), Some(ms(20))),
+            (Keystroke::Up(VirtualKey::SHIFT), None),
+        ]).unwrap();
+        sender.clock_mut().advance(ms(100));
+        sender.send(Keystroke::Tap(VirtualKey::KEY_B), None).unwrap();
+
+        assert_eq!(sender.events(), [
+            (ms(0), Keystroke::Down(VirtualKey::SHIFT)),
+            (ms(5), Keystroke::Tap(VirtualKey::KEY_A)),
+            (ms(25), Keystroke::Up(VirtualKey::SHIFT)),
+            (ms(125), Keystroke::Tap(VirtualKey::KEY_B)),
+        ]);
+    }
+
+    #[test]
+    fn test_send_schedule() {
+        let timed = crate::TypingScheduler::at_speed(10.0)
+            .schedule_keystrokes(&crate::to_keystrokes_new("abc").unwrap(), crate::KeyboardLayout::us());
+        let mut sender = RecordingSender::new();
+        sender.send_schedule(&timed).unwrap();
+
+        assert_eq!(sender.events()[2].0, ms(200));
+        assert_eq!(sender.clock().now(), ms(300));
+    }
+
+    #[test]
+    fn test_send_schedule_saturates() {
+        let timed = crate::TypingScheduler::at_speed(1e-30)
+            .schedule_keystrokes(&crate::to_keystrokes_new("abc").unwrap(), crate::KeyboardLayout::us());
+        let mut sender = RecordingSender::new();
+        sender.send_schedule(&timed).unwrap();
+
+        assert_eq!(sender.events()[1].0, Duration::MAX);
+        assert_eq!(sender.clock().now(), Duration::MAX);
+    }
+
+    #[test]
+    fn test_virtual_keyboard_is_a_sender() {
+        let mut keyboard = VirtualKeyboard::new(crate::KeyboardLayout::us());
+        type_greeting(&mut keyboard).unwrap();
+
+        assert_eq!(keyboard.text(), "Hi");
+    }
+}
@@ -0,0 +1,248 @@
+use std::ops::{Add, AddAssign, Range};
+
+use crate::keystroke::{decode_keystrokes, is_modifier, Keystroke};
+use crate::safety::HeldKeys;
+use crate::validate::{canonicalize, validate_keystrokes, SequenceError};
+use crate::KeyError;
+
+/// Keystrokes in the flat `Vec<u8>` format that are known to be well formed: every modifier is
+/// released by the end and no modifier is toggled without a key in between.
+///
+/// Concatenating, repeating, inserting and slicing keep that guarantee and join modifier runs across
+/// the seams, so `"ABC" + "DEF"` holds shift once rather than releasing and pressing it again.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct KeySequence(Vec<u8>);
+
+impl KeySequence {
+    pub fn new() -> KeySequence {
+        KeySequence::default()
+    }
+
+    /// Translates `keys` with [`crate::to_keystrokes_new`].
+    pub fn translate(keys: &str) -> Result<KeySequence, KeyError> {
+        crate::to_keystrokes_new(keys).map(KeySequence)
+    }
+
+    /// Validates flat keystrokes and removes redundant modifier toggles.
+    pub fn from_keystrokes(keystrokes: &[u8]) -> Result<KeySequence, SequenceError> {
+        validate_keystrokes(keystrokes)?;
+        Ok(KeySequence::from_events(&decode_keystrokes(keystrokes)))
+    }
+
+    /// The flat keystrokes, in the same format as [`crate::to_keystrokes_new`].
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_keystrokes(self) -> Vec<u8> {
+        self.0
+    }
+
+    /// The keystrokes as explicit events.
+    pub fn events(&self) -> Vec<Keystroke> {
+        decode_keystrokes(&self.0)
+    }
+
+    /// The number of keystrokes, modifiers included.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The number of keys that are not modifiers, which is the number of characters for translated
+    /// text. Positions passed to [`KeySequence::insert`] and [`KeySequence::slice`] count these keys.
+    pub fn key_count(&self) -> usize {
+        self.0.iter().filter(|&&vk| !is_modifier(vk.into())).count()
+    }
+
+    /// Appends `other`, merging a modifier run that spans the seam.
+    pub fn push(&mut self, other: &KeySequence) {
+        let mut events = self.events();
+        events.extend(other.events());
+        *self = KeySequence::from_events(&events);
+    }
+
+    /// The sequence repeated `count` times.
+    pub fn repeat(&self, count: usize) -> KeySequence {
+        let pass = self.events();
+        if pass.is_empty() {
+            return KeySequence::new();
+        }
+        let mut events = Vec::with_capacity(pass.len().saturating_mul(count));
+        for _ in 0..count {
+            events.extend_from_slice(&pass);
+        }
+        KeySequence::from_events(&events)
+    }
+
+    /// Inserts `other` before the key at `index`, see [`KeySequence::key_count`]. Modifiers held
+    /// around the insertion point are released for `other` and pressed again after it as needed.
+    ///
+    /// Panics when `index` is greater than the number of keys.
+    pub fn insert(&mut self, index: usize, other: &KeySequence) {
+        let key_count = self.key_count();
+        assert!(index <= key_count, "insertion index {index} is out of bounds for {key_count} keys");
+
+        let mut joined = self.slice(0..index);
+        joined.push(other);
+        joined.push(&self.slice(index..key_count));
+        *self = joined;
+    }
+
+    /// The keys in `range`, see [`KeySequence::key_count`], along with the modifiers they need.
+    ///
+    /// Panics when the range is out of bounds.
+    pub fn slice(&self, range: Range<usize>) -> KeySequence {
+        let events = self.events();
+        let keys: Vec<usize> = (0..events.len())
+            .filter(|&i| !is_modifier(events[i].vk()))
+            .collect();
+        let Range { start, end } = range;
+        assert!(start <= end && end <= keys.len(), "range {start}..{end} is out of bounds for {} keys", keys.len());
+        if start == end {
+            return KeySequence::new()
+        }
+
+        let mut held = HeldKeys::new();
+        for &keystroke in &events[..keys[start]] {
+            held.track(keystroke);
+        }
+
+        let mut sliced: Vec<Keystroke> = held.keys().iter().map(|&vk| Keystroke::Down(vk)).collect();
+        for &keystroke in &events[keys[start]..=keys[end - 1]] {
+            sliced.push(keystroke);
+            held.track(keystroke);
+        }
+        sliced.extend(held.release());
+        KeySequence::from_events(&sliced)
+    }
+
+    /// Builds a sequence from events that only contain modifier presses and releases, which balance
+    /// out, and taps.
+    fn from_events(events: &[Keystroke]) -> KeySequence {
+        KeySequence(canonicalize(events).into_iter().map(|keystroke| keystroke.vk().0).collect())
+    }
+}
+
+impl AsRef<[u8]> for KeySequence {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<KeySequence> for Vec<u8> {
+    fn from(sequence: KeySequence) -> Vec<u8> {
+        sequence.0
+    }
+}
+
+impl TryFrom<Vec<u8>> for KeySequence {
+    type Error = SequenceError;
+
+    fn try_from(keystrokes: Vec<u8>) -> Result<KeySequence, SequenceError> {
+        KeySequence::from_keystrokes(&keystrokes)
+    }
+}
+
+impl Add<&KeySequence> for KeySequence {
+    type Output = KeySequence;
+
+    fn add(mut self, other: &KeySequence) -> KeySequence {
+        self.push(other);
+        self
+    }
+}
+
+impl Add for KeySequence {
+    type Output = KeySequence;
+
+    fn add(self, other: KeySequence) -> KeySequence {
+        self + &other
+    }
+}
+
+impl AddAssign<&KeySequence> for KeySequence {
+    fn add_assign(&mut self, other: &KeySequence) {
+        self.push(other);
+    }
+}
+
+
+
+/* ### --- UNIT TEST --- ### */
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seq(text: &str) -> KeySequence {
+        KeySequence::translate(text).unwrap()
+    }
+
+    #[test]
+    fn test_concat_merges_seam() {
+        assert_eq!((seq("ABC") + seq("DEF")).as_bytes(), b"\x10ABCDEF\x10");
+        assert_eq!(seq("aB") + seq("Cd"), seq("aBCd"));
+        assert_eq!(seq("ab") + KeySequence::new(), seq("ab"));
+    }
+
+    #[test]
+    fn test_repeat() {
+        assert_eq!(seq("Ab").repeat(3), seq("AbAbAb"));
+        assert_eq!(seq("XY").repeat(2), seq("XYXY"));
+        assert!(seq("abc").repeat(0).is_empty());
+        assert!(KeySequence::new().repeat(usize::MAX).is_empty());
+    }
+
+    #[test]
+    fn test_slice() {
+        let text = "Hello, WORLD!";
+        let sequence = seq(text);
+
+        for start in 0..=text.len() {
+            for end in start..=text.len() {
+                assert_eq!(sequence.slice(start..end), seq(&text[start..end]), "{start}..{end}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut sequence = seq("ABCD");
+        sequence.insert(2, &seq("xy"));
+        assert_eq!(sequence, seq("ABxyCD"));
+
+        sequence.insert(0, &seq("Z"));
+        sequence.insert(sequence.key_count(), &seq("!"));
+        assert_eq!(sequence, seq("ZABxyCD!"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_insert_out_of_bounds() {
+        seq("ab").insert(3, &seq("c"));
+    }
+
+    #[test]
+    fn test_from_keystrokes_validates() {
+        assert_eq!(KeySequence::from_keystrokes(&[0x10, 0x41, 0x10, 0x10, 0x42, 0x10]).unwrap(), seq("AB"));
+        assert!(KeySequence::try_from(vec![0x10, 0x41]).is_err());
+    }
+
+    #[test]
+    fn test_results_are_well_formed() {
+        let sequence = (seq("aBc") + seq("D~")).repeat(3);
+        let mut inserted = sequence.slice(1..9);
+        inserted.insert(4, &seq("{X}"));
+
+        for keystrokes in [sequence, inserted] {
+            assert_eq!(crate::validate_keystrokes(keystrokes.as_bytes()), Ok(()));
+            assert_eq!(crate::canonicalize_keystrokes(keystrokes.as_bytes()), keystrokes.as_bytes());
+        }
+    }
+}
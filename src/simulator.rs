@@ -0,0 +1,353 @@
+use crate::keystroke::{is_modifier, Keystroke};
+use crate::layout::{to_unicode, DeadKey, KeyboardLayout, ShiftState};
+use crate::virtual_key::VirtualKey;
+
+/// Bit set in a [`VirtualKeyboard::keyboard_state`] entry while the key is down.
+pub const KEY_DOWN: u8 = 0x80;
+/// Bit flipped in a [`VirtualKeyboard::keyboard_state`] entry every time the key is pressed. Only
+/// meaningful for Caps Lock, Num Lock and Scroll Lock.
+pub const KEY_TOGGLED: u8 = 0x01;
+
+/// The outcome of replaying keystrokes on a [`VirtualKeyboard`].
+#[derive(PartialEq, Debug)]
+pub struct Replay {
+    /// The text typed into the focused window.
+    pub text: String,
+    /// Keys that were still held down once the keystrokes ran out, in the order they were pressed.
+    pub stuck_keys: Vec<VirtualKey>,
+}
+
+/// Simulates a Windows keyboard so keystrokes can be checked without a Windows machine.
+///
+/// The keyboard tracks the same key state win32's `GetKeyboardState` reports and types the characters
+/// `layout` produces into a text buffer. [`VirtualKey::BACK`] erases the last character,
+/// [`VirtualKey::RETURN`] and [`VirtualKey::TAB`] type `'\n'` and `'\t'`.
+///
+/// Dead keys wait for the next character and combine with it when the layout has a composition for
+/// the pair, otherwise both the accent and the character are typed. Erasing, enter and tab drop a
+/// waiting dead key.
+pub struct VirtualKeyboard<'a> {
+    layout: &'a KeyboardLayout,
+    state: [u8; 256],
+    held: Vec<VirtualKey>,
+    text: String,
+    dead_key: Option<&'a DeadKey>,
+}
+
+impl<'a> VirtualKeyboard<'a> {
+    /// Creates a keyboard with every key released and every lock off.
+    pub fn new(layout: &'a KeyboardLayout) -> VirtualKeyboard<'a> {
+        VirtualKeyboard {
+            layout,
+            state: [0; 256],
+            held: Vec::new(),
+            text: String::new(),
+            dead_key: None,
+        }
+    }
+
+    /// The key state array, laid out like win32's `GetKeyboardState`. See [`KEY_DOWN`] and
+    /// [`KEY_TOGGLED`].
+    pub fn keyboard_state(&self) -> &[u8; 256] {
+        &self.state
+    }
+
+    pub fn is_down(&self, vk: VirtualKey) -> bool {
+        self.state[vk.0 as usize] & KEY_DOWN != 0
+    }
+
+    pub fn is_toggled(&self, vk: VirtualKey) -> bool {
+        self.state[vk.0 as usize] & KEY_TOGGLED != 0
+    }
+
+    /// The modifiers currently held down.
+    pub fn shift_state(&self) -> ShiftState {
+        let mut shift_state = ShiftState::NONE;
+        if self.is_down(VirtualKey::SHIFT) { shift_state |= ShiftState::SHIFT; }
+        if self.is_down(VirtualKey::CONTROL) { shift_state |= ShiftState::CTRL; }
+        if self.is_down(VirtualKey::MENU) { shift_state |= ShiftState::ALT; }
+        shift_state
+    }
+
+    /// The text typed so far.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Keys currently held down, in the order they were pressed.
+    pub fn held_keys(&self) -> &[VirtualKey] {
+        &self.held
+    }
+
+    pub fn press(&mut self, keystroke: Keystroke) {
+        match keystroke {
+            Keystroke::Down(vk) | Keystroke::Repeat(vk) => self.key_down(vk),
+            Keystroke::Up(vk) => self.key_up(vk),
+            Keystroke::Tap(vk) => {
+                self.key_down(vk);
+                self.key_up(vk);
+            }
+        }
+    }
+
+    pub fn press_all(&mut self, keystrokes: &[Keystroke]) {
+        for &keystroke in keystrokes {
+            self.press(keystroke);
+        }
+    }
+
+    /// Types the flat output of [`crate::to_keystrokes_new`]. Modifiers toggle against the current
+    /// state of the keyboard, exactly like the README's `send_keystrokes` example.
+    pub fn type_keystrokes(&mut self, keystrokes: &[u8]) {
+        for &code in keystrokes {
+            let vk = VirtualKey(code);
+            if !is_modifier(vk) {
+                self.press(Keystroke::Tap(vk));
+            } else if self.held.contains(&vk) {
+                self.key_up(vk);
+            } else {
+                self.key_down(vk);
+            }
+        }
+    }
+
+    /// Presses `vk`. Pressing a key that is already down is treated as an auto-repeat and types its
+    /// character again.
+    pub fn key_down(&mut self, vk: VirtualKey) {
+        if !self.held.contains(&vk) {
+            self.held.push(vk);
+            self.state[vk.0 as usize] ^= KEY_TOGGLED;
+        }
+        self.state[vk.0 as usize] |= KEY_DOWN;
+        self.sync_generic_modifiers();
+        self.type_key(vk);
+    }
+
+    pub fn key_up(&mut self, vk: VirtualKey) {
+        self.held.retain(|&held| held != vk);
+        self.state[vk.0 as usize] &= !KEY_DOWN;
+        self.sync_generic_modifiers();
+    }
+
+    /// Stops the simulation, reporting the typed text and any keys left pressed.
+    pub fn finish(self) -> Replay {
+        Replay { text: self.text, stuck_keys: self.held }
+    }
+
+    /// Windows reports a generic modifier as down while either of its sides is, and a generic press is
+    /// treated as the left hand key.
+    fn sync_generic_modifiers(&mut self) {
+        const SIDES: [(VirtualKey, VirtualKey, VirtualKey); 3] = [
+            (VirtualKey::SHIFT, VirtualKey::LSHIFT, VirtualKey::RSHIFT),
+            (VirtualKey::CONTROL, VirtualKey::LCONTROL, VirtualKey::RCONTROL),
+            (VirtualKey::MENU, VirtualKey::LMENU, VirtualKey::RMENU),
+        ];
+        for (generic, left, right) in SIDES {
+            let left_down = self.held.contains(&generic) || self.held.contains(&left);
+            let right_down = self.held.contains(&right);
+            for (vk, down) in [(generic, left_down || right_down), (left, left_down), (right, right_down)] {
+                if down {
+                    self.state[vk.0 as usize] |= KEY_DOWN;
+                } else {
+                    self.state[vk.0 as usize] &= !KEY_DOWN;
+                }
+            }
+        }
+    }
+
+    fn type_key(&mut self, vk: VirtualKey) {
+        let shift_state = self.shift_state();
+        let is_typing = !shift_state.contains(ShiftState::CTRL) && !shift_state.contains(ShiftState::ALT);
+
+        match vk {
+            VirtualKey::BACK if is_typing => { self.dead_key = None; self.text.pop(); },
+            VirtualKey::RETURN if is_typing => { self.dead_key = None; self.text.push('\n') },
+            VirtualKey::TAB if is_typing => { self.dead_key = None; self.text.push('\t') },
+            _ => {
+                let layout = self.layout;
+                if let Some(dead_key) = layout.dead_key(vk, shift_state) {
+                    // Two dead keys in a row type both accents
+                    match self.dead_key.take() {
+                        Some(pending) => self.text.extend([accent(pending), accent(dead_key)].into_iter().flatten()),
+                        None => self.dead_key = Some(dead_key),
+                    }
+                } else if let Some(character) = self.character_for(vk, shift_state) {
+                    match self.dead_key.take() {
+                        Some(pending) => match pending.compositions.iter().find(|&&(base, _)| base == character) {
+                            Some(&(_, composed)) => self.text.push(composed),
+                            None => self.text.extend(accent(pending).into_iter().chain([character])),
+                        },
+                        None => self.text.push(character),
+                    }
+                }
+            }
+        }
+    }
+
+    fn character_for(&self, vk: VirtualKey, shift_state: ShiftState) -> Option<char> {
+        // The numpad only types digits while num lock is on
+        if (VirtualKey::NUMPAD0..=VirtualKey::NUMPAD9).contains(&vk) || vk == VirtualKey::DECIMAL {
+            if !self.is_toggled(VirtualKey::NUMLOCK) || !shift_state.is_empty() {
+                return None
+            }
+            return Some(if vk == VirtualKey::DECIMAL { '.' } else { (b'0' + vk.0 - VirtualKey::NUMPAD0.0) as char })
+        }
+
+        let character = to_unicode(vk, shift_state, self.layout)?;
+        if !self.is_toggled(VirtualKey::CAPITAL) || !character.is_alphabetic() {
+            return Some(character)
+        }
+
+        // Caps lock flips shift for letters only, like the CAPLOK attribute in Windows layouts
+        let flipped = ShiftState::from_bits(shift_state.bits() ^ ShiftState::SHIFT.bits());
+        Some(to_unicode(vk, flipped, self.layout)
+            .filter(|flipped| flipped.is_alphabetic())
+            .unwrap_or(character))
+    }
+}
+
+/// The character a dead key types on its own, which is its composition with space.
+fn accent(dead_key: &DeadKey) -> Option<char> {
+    dead_key.compositions.iter().find(|&&(base, _)| base == ' ').map(|&(_, accent)| accent)
+}
+
+/// Replays the flat output of [`crate::to_keystrokes_new`] on a fresh [`VirtualKeyboard`].
+pub fn replay(keystrokes: &[u8], layout: &KeyboardLayout) -> Replay {
+    let mut keyboard = VirtualKeyboard::new(layout);
+    keyboard.type_keystrokes(keystrokes);
+    keyboard.finish()
+}
+
+/// Decodes the flat output of [`crate::to_keystrokes_new`] back into the text it types.
+pub fn decode_text(keystrokes: &[u8], layout: &KeyboardLayout) -> String {
+    replay(keystrokes, layout).text
+}
+
+
+
+/* ### --- UNIT TEST --- ### */
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn us() -> &'static KeyboardLayout {
+        KeyboardLayout::us()
+    }
+
+    #[test]
+    fn test_round_trip_all_printable_ascii() {
+        let text: String = (0x20u8..0x7F).map(char::from).collect();
+        let strokes = crate::to_keystrokes_new(&text).unwrap();
+
+        let replay = replay(&strokes, us());
+        assert_eq!(replay.text, text);
+        assert!(replay.stuck_keys.is_empty());
+    }
+
+    #[test]
+    fn test_round_trip_mixed_case() {
+        let text = "ZbcAzCaZc Hello, World! (x_y) {a:b} <1|2>";
+        assert_eq!(decode_text(&crate::to_keystrokes_new(text).unwrap(), us()), text);
+    }
+
+    #[test]
+    fn test_stuck_shift_is_reported() {
+        let mut strokes = crate::to_keystrokes_new("AB").unwrap();
+        strokes.pop();
+
+        let replay = replay(&strokes, us());
+        assert_eq!(replay.text, "AB");
+        assert_eq!(replay.stuck_keys, vec![VirtualKey::SHIFT]);
+    }
+
+    #[test]
+    fn test_keyboard_state_tracks_sides() {
+        let mut keyboard = VirtualKeyboard::new(us());
+        keyboard.key_down(VirtualKey::RSHIFT);
+
+        assert!(keyboard.is_down(VirtualKey::SHIFT));
+        assert!(keyboard.is_down(VirtualKey::RSHIFT));
+        assert!(!keyboard.is_down(VirtualKey::LSHIFT));
+        assert_eq!(keyboard.keyboard_state()[VirtualKey::SHIFT.0 as usize] & KEY_DOWN, KEY_DOWN);
+
+        keyboard.key_up(VirtualKey::RSHIFT);
+        assert!(!keyboard.is_down(VirtualKey::SHIFT));
+    }
+
+    #[test]
+    fn test_generic_shift_is_left_hand() {
+        let mut keyboard = VirtualKeyboard::new(us());
+        keyboard.key_down(VirtualKey::SHIFT);
+
+        assert!(keyboard.is_down(VirtualKey::LSHIFT));
+        assert_eq!(keyboard.shift_state(), ShiftState::SHIFT);
+    }
+
+    #[test]
+    fn test_caps_lock_flips_letters_only() {
+        let mut keyboard = VirtualKeyboard::new(us());
+        keyboard.press(Keystroke::Tap(VirtualKey::CAPITAL));
+        assert!(keyboard.is_toggled(VirtualKey::CAPITAL));
+
+        keyboard.type_keystrokes(&crate::to_keystrokes_new("aB1!").unwrap());
+        assert_eq!(keyboard.text(), "Ab1!");
+    }
+
+    #[test]
+    fn test_num_lock_controls_numpad() {
+        let mut keyboard = VirtualKeyboard::new(us());
+        keyboard.press(Keystroke::Tap(VirtualKey::NUMPAD1));
+        keyboard.press(Keystroke::Tap(VirtualKey::NUMLOCK));
+        keyboard.press(Keystroke::Tap(VirtualKey::NUMPAD1));
+        keyboard.press(Keystroke::Tap(VirtualKey::DECIMAL));
+
+        assert_eq!(keyboard.text(), "1.");
+    }
+
+    #[test]
+    fn test_control_chords_do_not_type() {
+        let mut keyboard = VirtualKeyboard::new(us());
+        keyboard.press_all(&[
+            Keystroke::Down(VirtualKey::CONTROL),
+            Keystroke::Tap(VirtualKey::KEY_C),
+            Keystroke::Up(VirtualKey::CONTROL),
+            Keystroke::Tap(VirtualKey::KEY_C),
+        ]);
+
+        assert_eq!(keyboard.text(), "c");
+    }
+
+    #[test]
+    fn test_backspace_and_return() {
+        let mut keyboard = VirtualKeyboard::new(us());
+        keyboard.type_keystrokes(&crate::to_keystrokes_new("ab").unwrap());
+        keyboard.press(Keystroke::Tap(VirtualKey::BACK));
+        keyboard.press(Keystroke::Tap(VirtualKey::RETURN));
+
+        assert_eq!(keyboard.text(), "a\n");
+    }
+
+    #[test]
+    fn test_held_key_repeats() {
+        let mut keyboard = VirtualKeyboard::new(us());
+        keyboard.key_down(VirtualKey::KEY_X);
+        keyboard.key_down(VirtualKey::KEY_X);
+
+        let replay = keyboard.finish();
+        assert_eq!(replay.text, "xx");
+        assert_eq!(replay.stuck_keys, vec![VirtualKey::KEY_X]);
+    }
+
+    #[test]
+    fn test_dead_keys_compose() {
+        let german = KeyboardLayout::german();
+        let text = "Crème brûlée, 5^2";
+
+        assert_eq!(decode_text(&crate::to_keystrokes_with_layout(text, german).unwrap(), german), text);
+        // ^ followed by a key without a composition types both, two dead keys type both accents
+        assert_eq!(decode_text(&[0xDC, 0x4E, 0xDC, 0xDD], german), "^n^´");
+    }
+}
@@ -0,0 +1,183 @@
+use std::ops::Range;
+
+use crate::keystroke::ModifierEncoder;
+use crate::layout::{KeyboardLayout, ShiftState};
+use crate::virtual_key::VirtualKey;
+use crate::{ErrorCodes, KeyError};
+
+/// A keystroke along with the input character that produced it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MappedKeystroke {
+    pub vk: VirtualKey,
+    /// Index of the character in the input, counting `char`s.
+    pub char_index: usize,
+    /// Byte range of the character in the input.
+    pub bytes: Range<usize>,
+    /// Whether this is a modifier or dead key inserted for the character rather than its own key. A
+    /// modifier press belongs to the first character typed with it, a release to the last.
+    pub is_inserted: bool,
+}
+
+/// The flat keystrokes for a piece of text, each paired with the character that produced it.
+///
+/// When sending stops partway, [`SourceMap::progress`] tells how much of the text got through. To
+/// carry on, release the held keys with [`crate::cleanup_keystrokes`] and translate the text from
+/// [`SourceMap::resume_offset`]. A character composed with a dead key only counts as typed once its
+/// base key is sent; stopping between the two leaves the dead key pending on the target.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct SourceMap {
+    entries: Vec<MappedKeystroke>,
+    text_len: usize,
+}
+
+impl SourceMap {
+    /// Translates `keys` like [`crate::to_keystrokes_with_layout`], dead keys included.
+    pub fn new(keys: &str, layout: &KeyboardLayout) -> Result<SourceMap, KeyError> {
+        let mut keystrokes = Vec::with_capacity(keys.len());
+        let mut entries = Vec::with_capacity(keys.len());
+        let mut encoder = ModifierEncoder::new(&mut keystrokes);
+        let mut previous: Option<(usize, Range<usize>)> = None;
+
+        for (char_index, (start, character)) in keys.char_indices().enumerate() {
+            let plan = layout.keys_for(character).ok_or_else(|| KeyError::not_found(character))?;
+            let bytes = start..start + character.len_utf8();
+
+            // Only the last key, after any dead key, types the character
+            let mut keys = plan.keys().peekable();
+            while let Some((vk, shift_state)) = keys.next() {
+                let released = encoder.release(shift_state);
+                let pressed = encoder.press(shift_state);
+                encoder.push(vk, shift_state);
+
+                // Releases end the previous key's run, presses start this one's
+                if let Some((previous_index, previous_bytes)) = &previous {
+                    entries.extend((0..released).map(|_| (*previous_index, previous_bytes.clone(), true)));
+                }
+                entries.extend((0..pressed).map(|_| (char_index, bytes.clone(), true)));
+                entries.push((char_index, bytes.clone(), keys.peek().is_some()));
+                previous = Some((char_index, bytes.clone()));
+            }
+        }
+
+        let released = encoder.release(ShiftState::NONE);
+        if let Some((previous_index, previous_bytes)) = previous {
+            entries.extend((0..released).map(|_| (previous_index, previous_bytes.clone(), true)));
+        }
+
+        let entries = keystrokes.iter()
+            .zip(entries)
+            .map(|(&vk, (char_index, bytes, is_inserted))| MappedKeystroke { vk: VirtualKey(vk), char_index, bytes, is_inserted })
+            .collect();
+        Ok(SourceMap { entries, text_len: keys.len() })
+    }
+
+    /// Translates `keys` with the US layout. Errors match [`crate::to_keystrokes_new`], which reports
+    /// every character outside printable ASCII as [`ErrorCodes::OutOfRange`].
+    pub fn us(keys: &str) -> Result<SourceMap, KeyError> {
+        SourceMap::new(keys, KeyboardLayout::us())
+            .map_err(|error| KeyError { error_code: ErrorCodes::OutOfRange, ..error })
+    }
+
+    pub fn entries(&self) -> &[MappedKeystroke] {
+        &self.entries
+    }
+
+    /// The plain keystrokes, identical to the output of [`crate::to_keystrokes_new`] for US text.
+    pub fn keystrokes(&self) -> Vec<u8> {
+        self.entries.iter().map(|entry| entry.vk.0).collect()
+    }
+
+    /// The number of characters fully typed once the first `sent` keystrokes were sent. A
+    /// character counts once its own key is sent, even if a modifier is still to be released.
+    pub fn progress(&self, sent: usize) -> usize {
+        self.entries[..sent.min(self.entries.len())].iter()
+            .filter(|entry| !entry.is_inserted)
+            .count()
+    }
+
+    /// The byte offset in the input of the first character not yet typed after sending `sent`
+    /// keystrokes, or the input length when everything was typed.
+    pub fn resume_offset(&self, sent: usize) -> usize {
+        self.entries[sent.min(self.entries.len())..].iter()
+            .find(|entry| !entry.is_inserted)
+            .map_or(self.text_len, |entry| entry.bytes.start)
+    }
+}
+
+
+
+/* ### --- UNIT TEST --- ### */
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sources(map: &SourceMap) -> Vec<(usize, bool)> {
+        map.entries().iter().map(|entry| (entry.char_index, entry.is_inserted)).collect()
+    }
+
+    #[test]
+    fn test_matches_translator() {
+        let text = "ZbcAzCaZc Hello, World! {x} ~";
+
+        assert_eq!(SourceMap::us(text).unwrap().keystrokes(), crate::to_keystrokes_new(text).unwrap());
+    }
+
+    #[test]
+    fn test_modifiers_belong_to_their_characters() {
+        // ⇧↓ A B ⇧↑ c ⇧↓ D ⇧↑
+        let map = SourceMap::us("ABcD").unwrap();
+
+        assert_eq!(sources(&map), vec![(0, true), (0, false), (1, false), (1, true), (2, false), (3, true), (3, false), (3, true)]);
+    }
+
+    #[test]
+    fn test_byte_ranges() {
+        let mut layout_mappings = KeyboardLayout::us().mappings().to_vec();
+        layout_mappings.push(crate::KeyMapping { character: 'é', vk: VirtualKey::KEY_E, shift_state: ShiftState::CTRL | ShiftState::ALT });
+        let layout = KeyboardLayout::new("test", layout_mappings);
+        let map = SourceMap::new("aéb", &layout).unwrap();
+
+        let ranges: Vec<Range<usize>> = map.entries().iter().map(|entry| entry.bytes.clone()).collect();
+        assert_eq!(ranges, vec![0..1, 1..3, 1..3, 1..3, 1..3, 1..3, 3..4]);
+    }
+
+    #[test]
+    fn test_resume() {
+        let text = "Hello, World!";
+        let map = SourceMap::us(text).unwrap();
+        let keystrokes = map.keystrokes();
+
+        for sent in 0..=keystrokes.len() {
+            let mut keyboard = crate::VirtualKeyboard::new(KeyboardLayout::us());
+            keyboard.type_keystrokes(&keystrokes[..sent]);
+            keyboard.press_all(&crate::cleanup_keystrokes(&keystrokes, sent));
+            assert_eq!(keyboard.text().chars().count(), map.progress(sent));
+
+            keyboard.type_keystrokes(&crate::to_keystrokes_new(&text[map.resume_offset(sent)..]).unwrap());
+            assert_eq!(keyboard.finish().text, text, "sent {sent}");
+        }
+    }
+
+    #[test]
+    fn test_dead_keys() {
+        let german = KeyboardLayout::german();
+        // a ´ e ⇧↓ ´ ⇧↑ e
+        let map = SourceMap::new("aéè", german).unwrap();
+
+        assert_eq!(map.keystrokes(), crate::to_keystrokes_with_layout("aéè", german).unwrap());
+        assert_eq!(sources(&map), vec![(0, false), (1, true), (1, false), (2, true), (2, true), (2, true), (2, false)]);
+        assert_eq!(map.progress(2), 1);
+        assert_eq!(map.progress(3), 2);
+        assert_eq!(map.resume_offset(2), 1);
+    }
+
+    #[test]
+    fn test_unmapped_character() {
+        assert_eq!(SourceMap::us("aæ").unwrap_err(), KeyError { byte: 0xC3, error_code: ErrorCodes::OutOfRange });
+        assert_eq!(SourceMap::us("a\tb").unwrap_err(), crate::to_keystrokes_new("a\tb").unwrap_err());
+        assert_eq!(SourceMap::new("aæ", KeyboardLayout::us()).unwrap_err().error_code, ErrorCodes::NotFound);
+    }
+}
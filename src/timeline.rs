@@ -0,0 +1,207 @@
+use std::time::Duration;
+
+use crate::keystroke::Keystroke;
+use crate::validate::{PressTracker, SequenceErrorCodes};
+use crate::virtual_key::VirtualKey;
+
+/// Errors that can occur when flattening a [`Timeline`].
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TimelineErrorCodes {
+    /// A key is pressed or tapped while it is already held.
+    AlreadyPressed,
+    /// A key is released or repeated without being pressed first.
+    NotPressed,
+    /// A key is still held once the timeline ends.
+    StuckKey,
+}
+
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimelineError {
+    /// When the offending event happens, for [`TimelineErrorCodes::StuckKey`] when the key was pressed.
+    pub at: Duration,
+    /// The key the offending event applies to.
+    pub vk: VirtualKey,
+    /// Type of error that occurred.
+    pub error_code: TimelineErrorCodes
+}
+
+/// A script of key events at explicit times, measured from the start of the script.
+///
+/// Unlike the per-character output of the translator, holds can overlap, e.g. holding W while
+/// tapping Space and Shift. Events can be added in any order, [`Timeline::events`] sorts them and
+/// checks that every key is pressed before it is released and released before the script ends.
+/// Events at the same time keep the order they were added in.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Timeline {
+    events: Vec<(Duration, Keystroke)>,
+}
+
+impl Timeline {
+    pub fn new() -> Timeline {
+        Timeline::default()
+    }
+
+    /// Adds any event at `at`.
+    pub fn push(&mut self, at: Duration, keystroke: Keystroke) -> &mut Timeline {
+        self.events.push((at, keystroke));
+        self
+    }
+
+    pub fn press(&mut self, at: Duration, vk: VirtualKey) -> &mut Timeline {
+        self.push(at, Keystroke::Down(vk))
+    }
+
+    pub fn release(&mut self, at: Duration, vk: VirtualKey) -> &mut Timeline {
+        self.push(at, Keystroke::Up(vk))
+    }
+
+    pub fn tap(&mut self, at: Duration, vk: VirtualKey) -> &mut Timeline {
+        self.push(at, Keystroke::Tap(vk))
+    }
+
+    /// Presses `vk` at `from` and releases it at `to`.
+    pub fn hold(&mut self, vk: VirtualKey, from: Duration, to: Duration) -> &mut Timeline {
+        self.press(from, vk).release(to, vk)
+    }
+
+    /// The number of events added so far.
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// The time of the last event.
+    pub fn duration(&self) -> Duration {
+        self.events.iter().map(|&(at, _)| at).max().unwrap_or_default()
+    }
+
+    /// Flattens the script into events sorted by time, checking that presses and releases pair up
+    /// and nothing is left held at the end.
+    pub fn events(&self) -> Result<Vec<(Duration, Keystroke)>, TimelineError> {
+        let mut events = self.events.clone();
+        events.sort_by_key(|&(at, _)| at);
+
+        let mut tracker = PressTracker::new();
+        for &(at, keystroke) in &events {
+            if let Err(error_code) = tracker.track(keystroke, at) {
+                let error_code = match error_code {
+                    SequenceErrorCodes::AlreadyPressed => TimelineErrorCodes::AlreadyPressed,
+                    _ => TimelineErrorCodes::NotPressed,
+                };
+                return Err(TimelineError { at, vk: keystroke.vk(), error_code })
+            }
+        }
+
+        // Report the key that has been stuck the longest
+        if let Some((at, vk)) = tracker.stuck() {
+            return Err(TimelineError { at, vk, error_code: TimelineErrorCodes::StuckKey })
+        }
+
+        Ok(events)
+    }
+
+    /// Flattens the script like [`Timeline::events`], giving each event the time to wait after
+    /// sending it the same way [`crate::TypingScheduler::schedule`] does. The last event waits zero.
+    pub fn schedule(&self) -> Result<Vec<(Keystroke, Duration)>, TimelineError> {
+        let events = self.events()?;
+        Ok(events.iter()
+            .enumerate()
+            .map(|(i, &(at, keystroke))| {
+                let next = events.get(i + 1).map_or(at, |&(next, _)| next);
+                (keystroke, next - at)
+            })
+            .collect())
+    }
+}
+
+
+
+/* ### --- UNIT TEST --- ### */
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_overlapping_holds() {
+        let mut timeline = Timeline::new();
+        timeline
+            .hold(VirtualKey::KEY_W, ms(0), ms(1000))
+            .tap(ms(300), VirtualKey::SPACE)
+            .hold(VirtualKey::SHIFT, ms(500), ms(800));
+
+        assert_eq!(timeline.events().unwrap(), vec![
+            (ms(0), Keystroke::Down(VirtualKey::KEY_W)),
+            (ms(300), Keystroke::Tap(VirtualKey::SPACE)),
+            (ms(500), Keystroke::Down(VirtualKey::SHIFT)),
+            (ms(800), Keystroke::Up(VirtualKey::SHIFT)),
+            (ms(1000), Keystroke::Up(VirtualKey::KEY_W)),
+        ]);
+        assert_eq!(timeline.duration(), ms(1000));
+    }
+
+    #[test]
+    fn test_schedule_delays() {
+        let mut timeline = Timeline::new();
+        timeline.hold(VirtualKey::KEY_A, ms(10), ms(50)).tap(ms(20), VirtualKey::KEY_B);
+
+        assert_eq!(timeline.schedule().unwrap(), vec![
+            (Keystroke::Down(VirtualKey::KEY_A), ms(10)),
+            (Keystroke::Tap(VirtualKey::KEY_B), ms(30)),
+            (Keystroke::Up(VirtualKey::KEY_A), ms(0)),
+        ]);
+    }
+
+    #[test]
+    fn test_same_time_keeps_insertion_order() {
+        let mut timeline = Timeline::new();
+        timeline.hold(VirtualKey::KEY_A, ms(0), ms(100)).hold(VirtualKey::KEY_A, ms(100), ms(200));
+
+        assert!(timeline.events().is_ok());
+    }
+
+    #[test]
+    fn test_release_before_press() {
+        let mut timeline = Timeline::new();
+        timeline.hold(VirtualKey::KEY_D, ms(200), ms(100));
+
+        assert_eq!(timeline.events(), Err(TimelineError { at: ms(100), vk: VirtualKey::KEY_D, error_code: TimelineErrorCodes::NotPressed }));
+    }
+
+    #[test]
+    fn test_double_press() {
+        let mut timeline = Timeline::new();
+        timeline.hold(VirtualKey::KEY_D, ms(0), ms(100)).tap(ms(50), VirtualKey::KEY_D);
+
+        assert_eq!(timeline.events(), Err(TimelineError { at: ms(50), vk: VirtualKey::KEY_D, error_code: TimelineErrorCodes::AlreadyPressed }));
+    }
+
+    #[test]
+    fn test_stuck_key() {
+        let mut timeline = Timeline::new();
+        timeline.press(ms(40), VirtualKey::KEY_S).press(ms(10), VirtualKey::CONTROL).release(ms(60), VirtualKey::KEY_S);
+
+        assert_eq!(timeline.events(), Err(TimelineError { at: ms(10), vk: VirtualKey::CONTROL, error_code: TimelineErrorCodes::StuckKey }));
+    }
+
+    #[test]
+    fn test_repeats_need_a_held_key() {
+        let mut timeline = Timeline::new();
+        timeline.push(ms(0), Keystroke::Repeat(VirtualKey::BACK));
+
+        assert_eq!(timeline.events().unwrap_err().error_code, TimelineErrorCodes::NotPressed);
+    }
+}
@@ -0,0 +1,198 @@
+use std::time::Duration;
+
+use crate::keystroke::Keystroke;
+use crate::virtual_key::VirtualKey;
+
+/// The most repeats a single hold produces, over nine hours at the Windows default rate. Longer
+/// holds stop repeating there rather than building an enormous schedule.
+pub const MAX_REPEATS: usize = 1 << 20;
+
+/// Typematic settings, which control how a held key auto-repeats.
+///
+/// While a key is held Windows waits for the repeat delay, then sends another key-down with the repeat
+/// bit set at the repeat rate until the key is released. Holding Backspace to clear a field or an arrow
+/// key to scroll relies on this, and apps that check the repeat flag treat it differently from separate
+/// taps.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Typematic {
+    /// Time between pressing the key and the first repeat.
+    pub delay: Duration,
+    /// Repeats per second once repeating starts. Zero or a non-finite value disables repeating.
+    pub rate: f64,
+}
+
+impl Default for Typematic {
+    /// The Windows defaults, a delay of 500ms and about 30 repeats per second.
+    fn default() -> Self {
+        Typematic::new(Duration::from_millis(500), 30.0)
+    }
+}
+
+impl Typematic {
+    pub fn new(delay: Duration, rate: f64) -> Typematic {
+        Typematic { delay, rate }
+    }
+
+    /// The time between two repeats, or `None` when repeating is disabled. Rates so slow the interval
+    /// does not fit a [`Duration`] never repeat either.
+    pub fn interval(&self) -> Option<Duration> {
+        (self.rate > 0.0 && self.rate.is_finite()).then(|| Duration::try_from_secs_f64(1.0 / self.rate).ok()).flatten()
+    }
+
+    /// How many repeats a key held for `held` produces, at most [`MAX_REPEATS`].
+    pub fn repeat_count(&self, held: Duration) -> usize {
+        let Some(interval) = self.interval() else {
+            return 0
+        };
+        if held < self.delay || interval.is_zero() {
+            return 0
+        }
+        let repeats = 1 + (held - self.delay).as_nanos() / interval.as_nanos();
+        usize::try_from(repeats).map_or(MAX_REPEATS, |repeats| repeats.min(MAX_REPEATS))
+    }
+
+    /// Holds `vk` for `held`, producing a [`Keystroke::Down`], every [`Keystroke::Repeat`] that falls
+    /// within the hold and a final [`Keystroke::Up`]. Each event carries the time to wait after
+    /// sending it, like [`crate::TypingScheduler::schedule`], so the result can be spliced into a
+    /// schedule.
+    pub fn hold(&self, vk: VirtualKey, held: Duration) -> Vec<(Keystroke, Duration)> {
+        let repeats = self.repeat_count(held);
+        let mut timed = Vec::with_capacity(repeats + 2);
+
+        let mut last = Duration::ZERO;
+        let mut last_keystroke = Keystroke::Down(vk);
+        for i in 0..repeats {
+            let at = self.repeat_time(i);
+            timed.push((last_keystroke, at.saturating_sub(last)));
+            last = at;
+            last_keystroke = Keystroke::Repeat(vk);
+        }
+        timed.push((last_keystroke, held.saturating_sub(last)));
+        timed.push((Keystroke::Up(vk), Duration::ZERO));
+        timed
+    }
+
+    /// Holds `vk` just long enough for `repeats` auto-repeats, at most [`MAX_REPEATS`], then releases
+    /// it.
+    pub fn hold_repeats(&self, vk: VirtualKey, repeats: usize) -> Vec<(Keystroke, Duration)> {
+        let repeats = repeats.min(MAX_REPEATS);
+        let interval = match self.interval() {
+            Some(interval) if repeats > 0 && !interval.is_zero() => interval,
+            _ => return self.hold(vk, Duration::ZERO),
+        };
+
+        // Built directly rather than through hold, whose length would saturate for huge delays
+        let mut timed = Vec::with_capacity(repeats + 2);
+        timed.push((Keystroke::Down(vk), self.delay));
+        timed.extend(std::iter::repeat_n((Keystroke::Repeat(vk), interval), repeats - 1));
+        timed.push((Keystroke::Repeat(vk), Duration::ZERO));
+        timed.push((Keystroke::Up(vk), Duration::ZERO));
+        timed
+    }
+
+    /// When the repeat with index `i` is sent, measured from the key-down, [`Duration::MAX`] when
+    /// that is further away than a duration reaches.
+    fn repeat_time(&self, i: usize) -> Duration {
+        let i = u32::try_from(i).unwrap_or(u32::MAX);
+        let since_first = self.interval().unwrap_or_default().checked_mul(i).unwrap_or(Duration::MAX);
+        self.delay.saturating_add(since_first)
+    }
+}
+
+/// The untimed events of holding `vk` through `repeats` auto-repeats, ready to be mixed with the
+/// output of [`crate::decode_keystrokes`].
+pub fn hold_keystrokes(vk: VirtualKey, repeats: usize) -> Vec<Keystroke> {
+    let mut keystrokes = Vec::with_capacity(repeats + 2);
+    keystrokes.push(Keystroke::Down(vk));
+    keystrokes.extend(std::iter::repeat_n(Keystroke::Repeat(vk), repeats));
+    keystrokes.push(Keystroke::Up(vk));
+    keystrokes
+}
+
+
+
+/* ### --- UNIT TEST --- ### */
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_short_hold_does_not_repeat() {
+        let timed = Typematic::new(ms(250), 10.0).hold(VirtualKey::BACK, ms(100));
+
+        assert_eq!(timed, vec![
+            (Keystroke::Down(VirtualKey::BACK), ms(100)),
+            (Keystroke::Up(VirtualKey::BACK), Duration::ZERO),
+        ]);
+    }
+
+    #[test]
+    fn test_hold_repeats_at_rate() {
+        let timed = Typematic::new(ms(250), 10.0).hold(VirtualKey::BACK, ms(480));
+
+        assert_eq!(timed, vec![
+            (Keystroke::Down(VirtualKey::BACK), ms(250)),
+            (Keystroke::Repeat(VirtualKey::BACK), ms(100)),
+            (Keystroke::Repeat(VirtualKey::BACK), ms(100)),
+            (Keystroke::Repeat(VirtualKey::BACK), ms(30)),
+            (Keystroke::Up(VirtualKey::BACK), Duration::ZERO),
+        ]);
+        assert_eq!(crate::schedule::total_duration(&timed), ms(480));
+    }
+
+    #[test]
+    fn test_hold_repeats() {
+        let typematic = Typematic::new(ms(250), 10.0);
+        let timed = typematic.hold_repeats(VirtualKey::LEFT, 5);
+        let events: Vec<Keystroke> = timed.iter().map(|&(keystroke, _)| keystroke).collect();
+
+        assert_eq!(events, hold_keystrokes(VirtualKey::LEFT, 5));
+        assert_eq!(typematic.repeat_count(ms(650)), 5);
+    }
+
+    #[test]
+    fn test_disabled_rate() {
+        assert_eq!(Typematic::new(ms(0), 0.0).repeat_count(ms(10_000)), 0);
+        assert_eq!(Typematic::new(ms(0), f64::INFINITY).hold_repeats(VirtualKey::KEY_A, 3).len(), 2);
+        assert_eq!(Typematic::new(ms(0), 1e-30).interval(), None);
+        assert_eq!(Typematic::new(ms(0), 1e-30).repeat_count(ms(10_000)), 0);
+    }
+
+    #[test]
+    fn test_huge_durations_do_not_overflow() {
+        let timed = Typematic::new(Duration::MAX, 10.0).hold_repeats(VirtualKey::BACK, 2);
+        assert_eq!(timed.len(), 4);
+        assert_eq!(timed[0], (Keystroke::Down(VirtualKey::BACK), Duration::MAX));
+
+        let timed = Typematic::new(ms(0), 1e-19).hold_repeats(VirtualKey::BACK, 3);
+        assert_eq!(timed.len(), 5);
+        assert_eq!(crate::schedule::total_duration(&timed), Duration::MAX);
+    }
+
+    #[test]
+    fn test_repeat_count_is_bounded() {
+        let typematic = Typematic::new(ms(0), 1e9);
+
+        assert_eq!(typematic.repeat_count(Duration::MAX), MAX_REPEATS);
+        assert_eq!(typematic.hold(VirtualKey::BACK, Duration::from_secs(3600)).len(), MAX_REPEATS + 2);
+        assert_eq!(typematic.hold_repeats(VirtualKey::BACK, usize::MAX).len(), MAX_REPEATS + 2);
+    }
+
+    #[test]
+    fn test_repeats_type_on_the_simulator() {
+        let mut keyboard = crate::VirtualKeyboard::new(crate::KeyboardLayout::us());
+        keyboard.type_keystrokes(&crate::to_keystrokes_new("hello").unwrap());
+        keyboard.press_all(&hold_keystrokes(VirtualKey::BACK, 2));
+
+        let replay = keyboard.finish();
+        assert_eq!(replay.text, "he");
+        assert!(replay.stuck_keys.is_empty());
+    }
+}
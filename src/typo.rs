@@ -0,0 +1,197 @@
+use crate::geometry::PhysicalLayout;
+use crate::keystroke::ModifierEncoder;
+use crate::layout::{to_unicode, KeyPlan, KeyboardLayout, ShiftState};
+use crate::rng::SplitMix64;
+use crate::virtual_key::VirtualKey;
+use crate::KeyError;
+
+/// Keys physically next to `vk` on an ANSI keyboard running `layout`, including diagonal neighbours.
+/// Keys are found through their scan codes, so on German the neighbours of `Z` are those of US `Y`.
+pub fn adjacent_keys(vk: VirtualKey, layout: &KeyboardLayout) -> Vec<VirtualKey> {
+    PhysicalLayout::Ansi.adjacent_keys_with_layout(vk, layout)
+}
+
+/// Simulates a typist who sometimes hits a key next to the intended one, notices, erases it with
+/// [`VirtualKey::BACK`] and retypes. Once every correction is applied the typed text equals the
+/// input, which [`crate::replay`] can confirm. Characters composed with a dead key are always typed
+/// correctly.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TypoSimulator {
+    /// Chance, from `0.0` to `1.0`, that any one character is mistyped.
+    pub error_rate: f64,
+    /// The most characters typed after a mistake before it is noticed. Zero means every mistake is
+    /// corrected immediately.
+    pub max_notice_delay: usize,
+    /// The same seed always produces the same mistakes.
+    pub seed: u64,
+}
+
+impl TypoSimulator {
+    pub fn new(error_rate: f64, seed: u64) -> TypoSimulator {
+        TypoSimulator { error_rate, max_notice_delay: 0, seed }
+    }
+
+    /// Translates `keys` like [`crate::to_keystrokes_with_layout`], with mistakes and their
+    /// corrections mixed in.
+    pub fn to_keystrokes(&self, keys: &str, layout: &KeyboardLayout) -> Result<Vec<u8>, KeyError> {
+        let characters = keys.chars()
+            .map(|character| layout.keys_for(character).ok_or_else(|| KeyError::not_found(character)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let type_character = |encoder: &mut ModifierEncoder, plan: KeyPlan| {
+            for (vk, shift_state) in plan.keys() {
+                encoder.push(vk, shift_state);
+            }
+        };
+
+        let mut rng = SplitMix64::new(self.seed);
+        let mut keystrokes = Vec::with_capacity(characters.len());
+        let mut encoder = ModifierEncoder::new(&mut keystrokes);
+
+        let mut i = 0;
+        while i < characters.len() {
+            let plan = characters[i];
+            let typo = match plan {
+                KeyPlan::Mapped(mapping) if rng.next_f64() < self.error_rate => {
+                    pick_typo(&mut rng, mapping.vk, mapping.shift_state, layout).map(|typo| (typo, mapping.shift_state))
+                }
+                _ => None,
+            };
+            let Some((typo, shift_state)) = typo else {
+                type_character(&mut encoder, plan);
+                i += 1;
+                continue
+            };
+
+            // Keep typing for a few characters before noticing the mistake
+            let noticed_after = rng.next_index(self.max_notice_delay + 1).min(characters.len() - i - 1);
+            let retyped = &characters[i..=i + noticed_after];
+
+            encoder.push(typo, shift_state);
+            for &plan in &retyped[1..] {
+                type_character(&mut encoder, plan);
+            }
+            for _ in retyped {
+                encoder.push(VirtualKey::BACK, ShiftState::NONE);
+            }
+            for &plan in retyped {
+                type_character(&mut encoder, plan);
+            }
+            i += retyped.len();
+        }
+
+        encoder.finish();
+        Ok(keystrokes)
+    }
+}
+
+/// Picks a neighbouring key that types something under the same shift state.
+fn pick_typo(rng: &mut SplitMix64, vk: VirtualKey, shift_state: ShiftState, layout: &KeyboardLayout) -> Option<VirtualKey> {
+    let candidates: Vec<VirtualKey> = adjacent_keys(vk, layout).into_iter()
+        .filter(|&other| to_unicode(other, shift_state, layout).is_some())
+        .collect();
+    if candidates.is_empty() {
+        return None
+    }
+    Some(candidates[rng.next_index(candidates.len())])
+}
+
+
+
+/* ### --- UNIT TEST --- ### */
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorCodes;
+
+    const TEXT: &str = "The quick brown fox jumps over the lazy dog. THE END? {yes}";
+
+    #[test]
+    fn test_adjacent_keys() {
+        let mut keys = adjacent_keys(VirtualKey::KEY_S, KeyboardLayout::us());
+        keys.sort();
+
+        assert_eq!(keys, vec![VirtualKey::KEY_A, VirtualKey::KEY_D, VirtualKey::KEY_E, VirtualKey::KEY_W, VirtualKey::KEY_X, VirtualKey::KEY_Z]);
+    }
+
+    #[test]
+    fn test_adjacent_keys_follow_layout() {
+        let mut keys = adjacent_keys(VirtualKey::KEY_Z, KeyboardLayout::german());
+        keys.sort();
+
+        assert_eq!(keys, vec![VirtualKey::KEY_6, VirtualKey::KEY_7, VirtualKey::KEY_G, VirtualKey::KEY_H, VirtualKey::KEY_T, VirtualKey::KEY_U]);
+        assert!(adjacent_keys(VirtualKey::KEY_Y, KeyboardLayout::german()).contains(&VirtualKey::KEY_X));
+    }
+
+    #[test]
+    fn test_adjacent_to_space() {
+        let keys = adjacent_keys(VirtualKey::SPACE, KeyboardLayout::us());
+
+        assert_eq!(keys, vec![
+            VirtualKey::KEY_X, VirtualKey::KEY_C, VirtualKey::KEY_V, VirtualKey::KEY_B,
+            VirtualKey::KEY_N, VirtualKey::KEY_M, VirtualKey::OEM_COMMA, VirtualKey::LMENU, VirtualKey::RMENU,
+        ]);
+    }
+
+    #[test]
+    fn test_unknown_key_has_no_neighbours() {
+        assert!(adjacent_keys(VirtualKey::NUMPAD5, KeyboardLayout::us()).is_empty());
+    }
+
+    #[test]
+    fn test_no_errors_matches_translator() {
+        let strokes = TypoSimulator::new(0.0, 1).to_keystrokes(TEXT, KeyboardLayout::us()).unwrap();
+
+        assert_eq!(strokes, crate::to_keystrokes_new(TEXT).unwrap());
+    }
+
+    #[test]
+    fn test_corrections_restore_text() {
+        for seed in 0..50 {
+            let simulator = TypoSimulator { error_rate: 0.3, max_notice_delay: 3, seed };
+            let strokes = simulator.to_keystrokes(TEXT, KeyboardLayout::us()).unwrap();
+
+            let replay = crate::replay(&strokes, KeyboardLayout::us());
+            assert_eq!(replay.text, TEXT, "seed {seed}");
+            assert!(replay.stuck_keys.is_empty(), "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn test_dead_keys_restore_text() {
+        let text = "Café à la crème, s'il vous plaît. Où êtes-vous?";
+        let german = KeyboardLayout::german();
+
+        assert_eq!(TypoSimulator::new(0.0, 1).to_keystrokes(text, german), crate::to_keystrokes_with_layout(text, german));
+        for seed in 0..50 {
+            let simulator = TypoSimulator { error_rate: 0.3, max_notice_delay: 3, seed };
+            let strokes = simulator.to_keystrokes(text, german).unwrap();
+
+            assert_eq!(crate::replay(&strokes, german).text, text, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn test_mistakes_are_made() {
+        let strokes = TypoSimulator::new(1.0, 3).to_keystrokes("hello", KeyboardLayout::us()).unwrap();
+
+        assert_eq!(strokes.iter().filter(|&&vk| vk == VirtualKey::BACK.0).count(), 5);
+    }
+
+    #[test]
+    fn test_same_seed_same_mistakes() {
+        let simulator = TypoSimulator { error_rate: 0.2, max_notice_delay: 2, seed: 9 };
+
+        assert_eq!(simulator.to_keystrokes(TEXT, KeyboardLayout::us()).unwrap(),
+            simulator.to_keystrokes(TEXT, KeyboardLayout::us()).unwrap());
+    }
+
+    #[test]
+    fn test_unmapped_character() {
+        let err = TypoSimulator::new(0.5, 1).to_keystrokes("aæ", KeyboardLayout::us()).unwrap_err();
+
+        assert_eq!(err, KeyError { byte: 0xC3, error_code: ErrorCodes::NotFound });
+    }
+}
@@ -0,0 +1,211 @@
+use crate::keystroke::{decode_keystrokes, is_modifier, Keystroke};
+use crate::virtual_key::VirtualKey;
+
+/// Structural problems found by [`validate`].
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SequenceErrorCodes {
+    /// A key is pressed or tapped while it is already held, which leaves presses and releases
+    /// unbalanced.
+    AlreadyPressed,
+    /// A key is released without being pressed first.
+    OrphanRelease,
+    /// A key auto-repeats without being held.
+    OrphanRepeat,
+    /// A key is still held once the sequence ends.
+    StuckKey,
+}
+
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SequenceError {
+    /// Index of the offending event, for [`SequenceErrorCodes::StuckKey`] the press that was never
+    /// released. For the flat `Vec<u8>` format this is also the byte index.
+    pub position: usize,
+    /// The key the offending event applies to.
+    pub vk: VirtualKey,
+    /// Type of error that occurred.
+    pub error_code: SequenceErrorCodes
+}
+
+/// The keys held down while walking through a sequence, each with a mark for when it was pressed,
+/// such as its position or time. Shared by [`validate`] and [`crate::Timeline::events`].
+pub(crate) struct PressTracker<T> {
+    pressed_at: [Option<T>; 256],
+}
+
+impl<T: Copy + Ord> PressTracker<T> {
+    pub(crate) fn new() -> PressTracker<T> {
+        PressTracker { pressed_at: [None; 256] }
+    }
+
+    /// Applies `keystroke` happening at `at`, or reports why it cannot happen with the keys held.
+    pub(crate) fn track(&mut self, keystroke: Keystroke, at: T) -> Result<(), SequenceErrorCodes> {
+        let pressed = &mut self.pressed_at[keystroke.vk().0 as usize];
+        match (keystroke, *pressed) {
+            (Keystroke::Down(_), None) => { *pressed = Some(at); Ok(()) },
+            (Keystroke::Up(_), Some(_)) => { *pressed = None; Ok(()) },
+            (Keystroke::Tap(_), None) | (Keystroke::Repeat(_), Some(_)) => Ok(()),
+            (Keystroke::Down(_) | Keystroke::Tap(_), Some(_)) => Err(SequenceErrorCodes::AlreadyPressed),
+            (Keystroke::Up(_), None) => Err(SequenceErrorCodes::OrphanRelease),
+            (Keystroke::Repeat(_), None) => Err(SequenceErrorCodes::OrphanRepeat),
+        }
+    }
+
+    /// The key held the longest and when it was pressed, ties going to the lowest key code.
+    pub(crate) fn stuck(&self) -> Option<(T, VirtualKey)> {
+        (0..=255u8)
+            .filter_map(|code| self.pressed_at[code as usize].map(|at| (at, VirtualKey(code))))
+            .min_by_key(|&(at, _)| at)
+    }
+}
+
+/// Checks that every key is pressed before it is released and released before the sequence ends,
+/// returning the first problem found.
+pub fn validate(keystrokes: &[Keystroke]) -> Result<(), SequenceError> {
+    let mut tracker = PressTracker::new();
+    for (position, &keystroke) in keystrokes.iter().enumerate() {
+        tracker.track(keystroke, position)
+            .map_err(|error_code| SequenceError { position, vk: keystroke.vk(), error_code })?;
+    }
+
+    match tracker.stuck() {
+        Some((position, vk)) => Err(SequenceError { position, vk, error_code: SequenceErrorCodes::StuckKey }),
+        None => Ok(()),
+    }
+}
+
+/// Validates the flat output of [`crate::to_keystrokes_new`]. Modifiers toggle in this format, so
+/// the only possible problem is a modifier that appears an odd number of times.
+pub fn validate_keystrokes(keystrokes: &[u8]) -> Result<(), SequenceError> {
+    validate(&decode_keystrokes(keystrokes))
+}
+
+/// Removes modifier presses and releases that change nothing, such as a release immediately followed
+/// by a press of the same modifier. This coalesces runs of modifiers the same way
+/// [`crate::to_keystrokes_mut`] does.
+///
+/// A modifier pressed and released with no key in between is kept, since tapping Alt or Win on its
+/// own does something. Other modifier changes are applied right before the next key that is not a
+/// held modifier, in the order they last happened. The keys typed and the modifiers held for each of
+/// them are unchanged.
+pub fn canonicalize(keystrokes: &[Keystroke]) -> Vec<Keystroke> {
+    let mut canonical = Vec::with_capacity(keystrokes.len());
+    let mut held = [false; 256];
+    let mut pending: Vec<Keystroke> = Vec::new();
+
+    for &keystroke in keystrokes {
+        match keystroke {
+            Keystroke::Down(vk) | Keystroke::Up(vk) if is_modifier(vk) => pending.push(keystroke),
+            _ => {
+                flush(&mut pending, &mut held, &mut canonical);
+                canonical.push(keystroke);
+            }
+        }
+    }
+    flush(&mut pending, &mut held, &mut canonical);
+
+    canonical
+}
+
+/// Canonicalizes the flat output of [`crate::to_keystrokes_new`], for example after joining two
+/// sequences.
+pub fn canonicalize_keystrokes(keystrokes: &[u8]) -> Vec<u8> {
+    canonicalize(&decode_keystrokes(keystrokes)).into_iter()
+        .map(|keystroke| keystroke.vk().0)
+        .collect()
+}
+
+/// Emits the net effect of the pending modifier events, keeping modifiers tapped on their own.
+fn flush(pending: &mut Vec<Keystroke>, held: &mut [bool; 256], canonical: &mut Vec<Keystroke>) {
+    let mut is_tapped = [false; 256];
+    for (i, &keystroke) in pending.iter().enumerate() {
+        let vk = keystroke.vk();
+        let is_down = matches!(keystroke, Keystroke::Down(_));
+        if held[vk.0 as usize] == is_down {
+            continue
+        }
+
+        let is_last = pending[i + 1..].iter().all(|later| later.vk() != vk);
+        let starts_tap = is_down && pending[i + 1..].contains(&Keystroke::Up(vk));
+        if is_last || starts_tap || is_tapped[vk.0 as usize] {
+            is_tapped[vk.0 as usize] = starts_tap;
+            held[vk.0 as usize] = is_down;
+            canonical.push(keystroke);
+        }
+    }
+    pending.clear();
+}
+
+
+
+/* ### --- UNIT TEST --- ### */
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_notation;
+
+    fn events(notation: &str) -> Vec<Keystroke> {
+        parse_notation(notation).unwrap()
+    }
+
+    fn error(position: usize, vk: VirtualKey, error_code: SequenceErrorCodes) -> Result<(), SequenceError> {
+        Err(SequenceError { position, vk, error_code })
+    }
+
+    #[test]
+    fn test_translator_output_is_valid() {
+        assert_eq!(validate_keystrokes(&crate::to_keystrokes_new("Hello, World! {ABC}").unwrap()), Ok(()));
+    }
+
+    #[test]
+    fn test_structural_errors() {
+        assert_eq!(validate(&events("A ⇧↑")), error(1, VirtualKey::SHIFT, SequenceErrorCodes::OrphanRelease));
+        assert_eq!(validate(&events("⇧↓ A ⇧↓")), error(2, VirtualKey::SHIFT, SequenceErrorCodes::AlreadyPressed));
+        assert_eq!(validate(&events("⌫↻")), error(0, VirtualKey::BACK, SequenceErrorCodes::OrphanRepeat));
+        assert_eq!(validate(&events("W↓ ⇧↓ A ⇧↑")), error(0, VirtualKey::KEY_W, SequenceErrorCodes::StuckKey));
+    }
+
+    #[test]
+    fn test_odd_shift_count() {
+        let mut keystrokes = crate::to_keystrokes_new("aB").unwrap();
+        keystrokes.pop();
+
+        assert_eq!(validate_keystrokes(&keystrokes), error(1, VirtualKey::SHIFT, SequenceErrorCodes::StuckKey));
+    }
+
+    #[test]
+    fn test_canonicalize_coalesces_runs() {
+        assert_eq!(canonicalize(&events("⇧↓ A ⇧↑ ⇧↓ B ⇧↑ C")), events("⇧↓ A B ⇧↑ C"));
+        assert_eq!(canonicalize(&events("⇧↓ A ⇧↑ ⌃↓ ⇧↓ ⌃↑ B ⇧↑")), events("⇧↓ A ⌃↓ ⌃↑ B ⇧↑"));
+        assert_eq!(canonicalize(&events("⇧↓ ⇧↓ A ⇧↑ ⇧↑")), events("⇧↓ A ⇧↑"));
+    }
+
+    #[test]
+    fn test_canonicalize_joined_sequences() {
+        let mut joined = crate::to_keystrokes_new("aB").unwrap();
+        joined.extend(crate::to_keystrokes_new("Cd").unwrap());
+
+        assert_eq!(canonicalize_keystrokes(&joined), crate::to_keystrokes_new("aBCd").unwrap());
+    }
+
+    #[test]
+    fn test_canonical_translator_output_is_unchanged() {
+        let keystrokes = crate::to_keystrokes_new("ZbcAzCaZc Hello, World! {x} ~").unwrap();
+
+        assert_eq!(canonicalize_keystrokes(&keystrokes), keystrokes);
+    }
+
+    #[test]
+    fn test_canonicalize_keeps_modifier_taps_and_held_keys() {
+        for notation in ["W↓ ⎇↓ ⎇↑ ⎇ W↑", "A L⊞↓ L⊞↑ B", "⌃↓ ⇧↓ ⌃↑ X ⇧↑", "⇧↓ ⇧↑ ⇧↓ A ⇧↑"] {
+            assert_eq!(canonicalize(&events(notation)), events(notation), "{notation}");
+        }
+        assert_eq!(canonicalize_keystrokes(&[0x12, 0x12]), [0x12, 0x12]);
+    }
+}
@@ -0,0 +1,352 @@
+use std::fmt;
+
+// A helpful source: http://www.kbdedit.com/manual/low_level_vk_list.html
+// See: https://learn.microsoft.com/en-us/windows/win32/inputdev/virtual-key-codes
+
+/// A Windows virtual-key code.
+///
+/// The crate's translation functions emit raw `u8` codes, this type wraps the same value so it can be
+/// named, compared against the constants below and printed.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct VirtualKey(pub u8);
+
+macro_rules! virtual_keys {
+    ($($konst:ident = $code:literal => $name:literal,)*) => {
+        impl VirtualKey {
+            $(pub const $konst: VirtualKey = VirtualKey($code);)*
+        }
+
+        /// Every named virtual key in ascending code order.
+        static NAMES: &[(u8, &str)] = &[$(($code, $name),)*];
+    };
+}
+
+virtual_keys! {
+    LBUTTON = 0x01 => "VK_LBUTTON",
+    RBUTTON = 0x02 => "VK_RBUTTON",
+    CANCEL = 0x03 => "VK_CANCEL",
+    MBUTTON = 0x04 => "VK_MBUTTON",
+    XBUTTON1 = 0x05 => "VK_XBUTTON1",
+    XBUTTON2 = 0x06 => "VK_XBUTTON2",
+    BACK = 0x08 => "VK_BACK",
+    TAB = 0x09 => "VK_TAB",
+    CLEAR = 0x0C => "VK_CLEAR",
+    RETURN = 0x0D => "VK_RETURN",
+    SHIFT = 0x10 => "VK_SHIFT",
+    CONTROL = 0x11 => "VK_CONTROL",
+    MENU = 0x12 => "VK_MENU",
+    PAUSE = 0x13 => "VK_PAUSE",
+    CAPITAL = 0x14 => "VK_CAPITAL",
+    KANA = 0x15 => "VK_KANA",
+    IME_ON = 0x16 => "VK_IME_ON",
+    JUNJA = 0x17 => "VK_JUNJA",
+    FINAL = 0x18 => "VK_FINAL",
+    KANJI = 0x19 => "VK_KANJI",
+    IME_OFF = 0x1A => "VK_IME_OFF",
+    ESCAPE = 0x1B => "VK_ESCAPE",
+    CONVERT = 0x1C => "VK_CONVERT",
+    NONCONVERT = 0x1D => "VK_NONCONVERT",
+    ACCEPT = 0x1E => "VK_ACCEPT",
+    MODECHANGE = 0x1F => "VK_MODECHANGE",
+    SPACE = 0x20 => "VK_SPACE",
+    PRIOR = 0x21 => "VK_PRIOR",
+    NEXT = 0x22 => "VK_NEXT",
+    END = 0x23 => "VK_END",
+    HOME = 0x24 => "VK_HOME",
+    LEFT = 0x25 => "VK_LEFT",
+    UP = 0x26 => "VK_UP",
+    RIGHT = 0x27 => "VK_RIGHT",
+    DOWN = 0x28 => "VK_DOWN",
+    SELECT = 0x29 => "VK_SELECT",
+    PRINT = 0x2A => "VK_PRINT",
+    EXECUTE = 0x2B => "VK_EXECUTE",
+    SNAPSHOT = 0x2C => "VK_SNAPSHOT",
+    INSERT = 0x2D => "VK_INSERT",
+    DELETE = 0x2E => "VK_DELETE",
+    HELP = 0x2F => "VK_HELP",
+    KEY_0 = 0x30 => "VK_0",
+    KEY_1 = 0x31 => "VK_1",
+    KEY_2 = 0x32 => "VK_2",
+    KEY_3 = 0x33 => "VK_3",
+    KEY_4 = 0x34 => "VK_4",
+    KEY_5 = 0x35 => "VK_5",
+    KEY_6 = 0x36 => "VK_6",
+    KEY_7 = 0x37 => "VK_7",
+    KEY_8 = 0x38 => "VK_8",
+    KEY_9 = 0x39 => "VK_9",
+    KEY_A = 0x41 => "VK_A",
+    KEY_B = 0x42 => "VK_B",
+    KEY_C = 0x43 => "VK_C",
+    KEY_D = 0x44 => "VK_D",
+    KEY_E = 0x45 => "VK_E",
+    KEY_F = 0x46 => "VK_F",
+    KEY_G = 0x47 => "VK_G",
+    KEY_H = 0x48 => "VK_H",
+    KEY_I = 0x49 => "VK_I",
+    KEY_J = 0x4A => "VK_J",
+    KEY_K = 0x4B => "VK_K",
+    KEY_L = 0x4C => "VK_L",
+    KEY_M = 0x4D => "VK_M",
+    KEY_N = 0x4E => "VK_N",
+    KEY_O = 0x4F => "VK_O",
+    KEY_P = 0x50 => "VK_P",
+    KEY_Q = 0x51 => "VK_Q",
+    KEY_R = 0x52 => "VK_R",
+    KEY_S = 0x53 => "VK_S",
+    KEY_T = 0x54 => "VK_T",
+    KEY_U = 0x55 => "VK_U",
+    KEY_V = 0x56 => "VK_V",
+    KEY_W = 0x57 => "VK_W",
+    KEY_X = 0x58 => "VK_X",
+    KEY_Y = 0x59 => "VK_Y",
+    KEY_Z = 0x5A => "VK_Z",
+    LWIN = 0x5B => "VK_LWIN",
+    RWIN = 0x5C => "VK_RWIN",
+    APPS = 0x5D => "VK_APPS",
+    SLEEP = 0x5F => "VK_SLEEP",
+    NUMPAD0 = 0x60 => "VK_NUMPAD0",
+    NUMPAD1 = 0x61 => "VK_NUMPAD1",
+    NUMPAD2 = 0x62 => "VK_NUMPAD2",
+    NUMPAD3 = 0x63 => "VK_NUMPAD3",
+    NUMPAD4 = 0x64 => "VK_NUMPAD4",
+    NUMPAD5 = 0x65 => "VK_NUMPAD5",
+    NUMPAD6 = 0x66 => "VK_NUMPAD6",
+    NUMPAD7 = 0x67 => "VK_NUMPAD7",
+    NUMPAD8 = 0x68 => "VK_NUMPAD8",
+    NUMPAD9 = 0x69 => "VK_NUMPAD9",
+    MULTIPLY = 0x6A => "VK_MULTIPLY",
+    ADD = 0x6B => "VK_ADD",
+    SEPARATOR = 0x6C => "VK_SEPARATOR",
+    SUBTRACT = 0x6D => "VK_SUBTRACT",
+    DECIMAL = 0x6E => "VK_DECIMAL",
+    DIVIDE = 0x6F => "VK_DIVIDE",
+    F1 = 0x70 => "VK_F1",
+    F2 = 0x71 => "VK_F2",
+    F3 = 0x72 => "VK_F3",
+    F4 = 0x73 => "VK_F4",
+    F5 = 0x74 => "VK_F5",
+    F6 = 0x75 => "VK_F6",
+    F7 = 0x76 => "VK_F7",
+    F8 = 0x77 => "VK_F8",
+    F9 = 0x78 => "VK_F9",
+    F10 = 0x79 => "VK_F10",
+    F11 = 0x7A => "VK_F11",
+    F12 = 0x7B => "VK_F12",
+    F13 = 0x7C => "VK_F13",
+    F14 = 0x7D => "VK_F14",
+    F15 = 0x7E => "VK_F15",
+    F16 = 0x7F => "VK_F16",
+    F17 = 0x80 => "VK_F17",
+    F18 = 0x81 => "VK_F18",
+    F19 = 0x82 => "VK_F19",
+    F20 = 0x83 => "VK_F20",
+    F21 = 0x84 => "VK_F21",
+    F22 = 0x85 => "VK_F22",
+    F23 = 0x86 => "VK_F23",
+    F24 = 0x87 => "VK_F24",
+    NUMLOCK = 0x90 => "VK_NUMLOCK",
+    SCROLL = 0x91 => "VK_SCROLL",
+    LSHIFT = 0xA0 => "VK_LSHIFT",
+    RSHIFT = 0xA1 => "VK_RSHIFT",
+    LCONTROL = 0xA2 => "VK_LCONTROL",
+    RCONTROL = 0xA3 => "VK_RCONTROL",
+    LMENU = 0xA4 => "VK_LMENU",
+    RMENU = 0xA5 => "VK_RMENU",
+    BROWSER_BACK = 0xA6 => "VK_BROWSER_BACK",
+    BROWSER_FORWARD = 0xA7 => "VK_BROWSER_FORWARD",
+    BROWSER_REFRESH = 0xA8 => "VK_BROWSER_REFRESH",
+    BROWSER_STOP = 0xA9 => "VK_BROWSER_STOP",
+    BROWSER_SEARCH = 0xAA => "VK_BROWSER_SEARCH",
+    BROWSER_FAVORITES = 0xAB => "VK_BROWSER_FAVORITES",
+    BROWSER_HOME = 0xAC => "VK_BROWSER_HOME",
+    VOLUME_MUTE = 0xAD => "VK_VOLUME_MUTE",
+    VOLUME_DOWN = 0xAE => "VK_VOLUME_DOWN",
+    VOLUME_UP = 0xAF => "VK_VOLUME_UP",
+    MEDIA_NEXT_TRACK = 0xB0 => "VK_MEDIA_NEXT_TRACK",
+    MEDIA_PREV_TRACK = 0xB1 => "VK_MEDIA_PREV_TRACK",
+    MEDIA_STOP = 0xB2 => "VK_MEDIA_STOP",
+    MEDIA_PLAY_PAUSE = 0xB3 => "VK_MEDIA_PLAY_PAUSE",
+    LAUNCH_MAIL = 0xB4 => "VK_LAUNCH_MAIL",
+    LAUNCH_MEDIA_SELECT = 0xB5 => "VK_LAUNCH_MEDIA_SELECT",
+    LAUNCH_APP1 = 0xB6 => "VK_LAUNCH_APP1",
+    LAUNCH_APP2 = 0xB7 => "VK_LAUNCH_APP2",
+    OEM_1 = 0xBA => "VK_OEM_1",
+    OEM_PLUS = 0xBB => "VK_OEM_PLUS",
+    OEM_COMMA = 0xBC => "VK_OEM_COMMA",
+    OEM_MINUS = 0xBD => "VK_OEM_MINUS",
+    OEM_PERIOD = 0xBE => "VK_OEM_PERIOD",
+    OEM_2 = 0xBF => "VK_OEM_2",
+    OEM_3 = 0xC0 => "VK_OEM_3",
+    OEM_4 = 0xDB => "VK_OEM_4",
+    OEM_5 = 0xDC => "VK_OEM_5",
+    OEM_6 = 0xDD => "VK_OEM_6",
+    OEM_7 = 0xDE => "VK_OEM_7",
+    OEM_8 = 0xDF => "VK_OEM_8",
+    OEM_102 = 0xE2 => "VK_OEM_102",
+    PROCESSKEY = 0xE5 => "VK_PROCESSKEY",
+    PACKET = 0xE7 => "VK_PACKET",
+    ATTN = 0xF6 => "VK_ATTN",
+    CRSEL = 0xF7 => "VK_CRSEL",
+    EXSEL = 0xF8 => "VK_EXSEL",
+    EREOF = 0xF9 => "VK_EREOF",
+    PLAY = 0xFA => "VK_PLAY",
+    ZOOM = 0xFB => "VK_ZOOM",
+    NONAME = 0xFC => "VK_NONAME",
+    PA1 = 0xFD => "VK_PA1",
+    OEM_CLEAR = 0xFE => "VK_OEM_CLEAR",
+}
+
+impl VirtualKey {
+    /// The raw virtual-key code.
+    pub const fn code(self) -> u8 {
+        self.0
+    }
+
+    /// The Win32 constant name of the key, e.g. `"VK_SHIFT"`. Returns `None` for unassigned and
+    /// reserved codes.
+    pub fn name(self) -> Option<&'static str> {
+        NAMES.binary_search_by_key(&self.0, |&(code, _)| code)
+            .ok()
+            .map(|i| NAMES[i].1)
+    }
+
+    /// Looks up a key by its Win32 constant name. The `VK_` prefix is optional and the comparison
+    /// ignores ASCII case, so `"VK_SHIFT"`, `"shift"` and `"Shift"` all match.
+    pub fn from_name(name: &str) -> Option<VirtualKey> {
+        let name = name.strip_prefix("VK_")
+            .or_else(|| name.strip_prefix("vk_"))
+            .unwrap_or(name);
+        NAMES.iter()
+            .find(|(_, known)| known[3..].eq_ignore_ascii_case(name))
+            .map(|&(code, _)| VirtualKey(code))
+    }
+
+    /// Parses the output of the [`fmt::Display`] impl: a name accepted by [`VirtualKey::from_name`] or
+    /// a hex code such as `0x41`.
+    pub fn parse(text: &str) -> Option<VirtualKey> {
+        match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+            Some(hex) => u8::from_str_radix(hex, 16).ok().map(VirtualKey),
+            None => VirtualKey::from_name(text),
+        }
+    }
+}
+
+/// Writes the Win32 constant name of the key, or its hex code when the key has no name.
+impl fmt::Display for VirtualKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => f.write_str(name),
+            None => write!(f, "{:#04X}", self.0),
+        }
+    }
+}
+
+/// Keys serialize by name so stored files stay readable and do not depend on the numeric codes.
+#[cfg(feature = "serde")]
+impl serde::Serialize for VirtualKey {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Accepts anything [`VirtualKey::parse`] does, as well as a plain integer code.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for VirtualKey {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl serde::de::Visitor<'_> for Visitor {
+            type Value = VirtualKey;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a virtual key name or code")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, text: &str) -> Result<VirtualKey, E> {
+                VirtualKey::parse(text)
+                    .ok_or_else(|| E::invalid_value(serde::de::Unexpected::Str(text), &self))
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, code: u64) -> Result<VirtualKey, E> {
+                u8::try_from(code)
+                    .map(VirtualKey)
+                    .map_err(|_| E::invalid_value(serde::de::Unexpected::Unsigned(code), &self))
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+impl From<u8> for VirtualKey {
+    fn from(code: u8) -> Self {
+        VirtualKey(code)
+    }
+}
+
+impl From<VirtualKey> for u8 {
+    fn from(vk: VirtualKey) -> Self {
+        vk.0
+    }
+}
+
+
+
+/* ### --- UNIT TEST --- ### */
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_are_sorted_for_binary_search() {
+        assert!(NAMES.windows(2).all(|pair| pair[0].0 < pair[1].0), "name table must be sorted by code");
+    }
+
+    #[test]
+    fn test_name_of_shift() {
+        assert_eq!(VirtualKey::SHIFT.name(), Some("VK_SHIFT"));
+        assert_eq!(VirtualKey(0x41).name(), Some("VK_A"));
+    }
+
+    #[test]
+    fn test_unassigned_code_has_no_name() {
+        assert_eq!(VirtualKey(0x07).name(), None);
+        assert_eq!(VirtualKey(0xFF).name(), None);
+    }
+
+    #[test]
+    fn test_from_name_round_trip() {
+        for &(code, name) in NAMES {
+            assert_eq!(VirtualKey::from_name(name), Some(VirtualKey(code)), "{name} did not round trip");
+        }
+    }
+
+    #[test]
+    fn test_display_and_parse() {
+        assert_eq!(VirtualKey::OEM_PLUS.to_string(), "VK_OEM_PLUS");
+        assert_eq!(VirtualKey(0x07).to_string(), "0x07");
+        assert_eq!(VirtualKey::parse("0x07"), Some(VirtualKey(0x07)));
+        assert_eq!(VirtualKey::parse("VK_OEM_PLUS"), Some(VirtualKey::OEM_PLUS));
+        assert_eq!(VirtualKey::parse("0x100"), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_by_name() {
+        assert_eq!(serde_json::to_string(&VirtualKey::SHIFT).unwrap(), r#""VK_SHIFT""#);
+        assert_eq!(serde_json::to_string(&VirtualKey(0xFF)).unwrap(), r#""0xFF""#);
+        assert_eq!(serde_json::from_str::<VirtualKey>(r#""shift""#).unwrap(), VirtualKey::SHIFT);
+        assert_eq!(serde_json::from_str::<VirtualKey>("65").unwrap(), VirtualKey::KEY_A);
+        assert!(serde_json::from_str::<VirtualKey>(r#""VK_NOPE""#).is_err());
+        assert!(serde_json::from_str::<VirtualKey>("256").is_err());
+    }
+
+    #[test]
+    fn test_from_name_without_prefix() {
+        assert_eq!(VirtualKey::from_name("oem_plus"), Some(VirtualKey::OEM_PLUS));
+        assert_eq!(VirtualKey::from_name("VK_NOPE"), None);
+    }
+}